@@ -9,7 +9,7 @@ import { WindowTracker } from './windowTracker.js';
 import { FocusManager } from './focusManager.js';
 import { Renderer } from './renderer.js';
 import { IdleMonitor } from './idleMonitor.js';
-import { updateTerminalWmClasses } from './constants.js';
+import { updateTerminalWmClasses, PROTOCOL_VERSION } from './constants.js';
 
 export const AgentsView = GObject.registerClass(
 class AgentsView extends St.BoxLayout {
@@ -23,6 +23,7 @@ class AgentsView extends St.BoxLayout {
 
         this._settings = settings;
         this._agents = [];
+        this._refusedReason = null;
         this._cancellable = new Gio.Cancellable();
 
         this._setupSettings();
@@ -209,17 +210,29 @@ class AgentsView extends St.BoxLayout {
             this._daemon.connect('connected', () => {
                 if (this._daemonStatusItem)
                     this._daemonStatusItem.label.text = 'Daemon: connected';
+                this._daemon.send({
+                    type: 'hello',
+                    protocol_version: PROTOCOL_VERSION,
+                    client: 'extension',
+                    capabilities: [],
+                });
                 this._focusManager.resetWorkspaceCache();
-                this._onFocusWindowChanged();
-                this._focusManager.sendAllWorkspaces((msg) => this._daemon.send(msg));
-                this._sendAutoFocusConfig();
+                const messages = [];
+                const collect = (msg) => messages.push(msg);
+                this._onFocusWindowChanged(collect);
+                this._focusManager.sendAllWorkspaces(collect);
+                messages.push(this._autoFocusConfigMessage());
+                this._daemon.send({ type: 'batch', messages });
             })
         );
 
         this._daemonSignals.push(
             this._daemon.connect('disconnected', () => {
-                if (this._daemonStatusItem)
-                    this._daemonStatusItem.label.text = 'Daemon: disconnected';
+                if (this._daemonStatusItem) {
+                    this._daemonStatusItem.label.text = this._refusedReason
+                        ? `Daemon: refused (${this._refusedReason})`
+                        : 'Daemon: disconnected';
+                }
                 this._agents = [];
                 this._updateDots();
             })
@@ -238,11 +251,14 @@ class AgentsView extends St.BoxLayout {
         };
 
         this._windowTracker.onWorkspaceChanged = () => {
-            this._focusManager.sendAllWorkspaces((msg) => this._daemon.send(msg));
-            this._focusManager.sendWindowFocus(
-                global.display.get_focus_window(),
-                (msg) => this._daemon.send(msg)
-            );
+            const messages = [];
+            const collect = (msg) => messages.push(msg);
+            const win = global.display.get_focus_window();
+            this._focusManager.sendAllWorkspaces(collect);
+            this._focusManager.sendLocation(win, collect);
+            this._focusManager.sendWindowFocus(win, collect);
+            if (messages.length > 0)
+                this._daemon.send({ type: 'batch', messages });
         };
 
         this._windowTracker.onWindowUnmanaged = (session) => {
@@ -259,22 +275,27 @@ class AgentsView extends St.BoxLayout {
         this._idleMonitor.onActive = () => this._daemon.send({ type: 'idle_status', idle: false });
     }
 
-    _sendAutoFocusConfig() {
-        this._daemon.send({
+    _autoFocusConfigMessage() {
+        return {
             type: 'auto_focus_config',
             enabled: this._autoFocusEnabled,
             focus_delay_ms: this._focusDelayMs,
-        });
+        };
     }
 
-    _onFocusWindowChanged() {
+    _sendAutoFocusConfig() {
+        this._daemon.send(this._autoFocusConfigMessage());
+    }
+
+    _onFocusWindowChanged(send = (msg) => this._daemon.send(msg)) {
         const win = global.display.get_focus_window();
 
+        this._focusManager.sendLocation(win, send);
         if (!win)
             return;
 
-        this._focusManager.sendWindowFocus(win, (msg) => this._daemon.send(msg));
-        this._focusManager.sendWorkspaceForWindow(win, (msg) => this._daemon.send(msg));
+        this._focusManager.sendWindowFocus(win, send);
+        this._focusManager.sendWorkspaceForWindow(win, send);
         this._focusManager.updateOriginalWorkspace(win, this._agents);
     }
 
@@ -290,8 +311,17 @@ class AgentsView extends St.BoxLayout {
                 this._focusManager.focusWindow(msg.session, msg.agent_type);
             } else if (msg.type === 'auto_focus') {
                 this._focusManager.handleAutoFocus(msg.session, msg.agent_type);
+            } else if (msg.type === 'dnd_ended') {
+                this._agents = msg.agents;
+                this._updateDots();
             } else if (msg.type === 'return_workspace') {
-                this._focusManager.returnWorkspace();
+                this._focusManager.returnWorkspace(msg.destination);
+            } else if (msg.type === 'refused') {
+                console.warn(`Argus Agenticus: daemon refused connection: ${msg.reason}`);
+                this._refusedReason = msg.reason;
+                this._daemon.halt();
+                if (this._daemonStatusItem)
+                    this._daemonStatusItem.label.text = `Daemon: refused (${msg.reason})`;
             }
         } catch (e) {
             logError(e, 'Failed to parse daemon message');
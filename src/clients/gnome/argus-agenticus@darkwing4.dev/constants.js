@@ -20,3 +20,4 @@ export const MARGIN_SAME_GROUP = 0;
 export const MARGIN_DIFFERENT_GROUP = 6;
 export const CLICK_PADDING = 8;
 export const RECONNECT_DELAY = 3000;
+export const PROTOCOL_VERSION = 1;
@@ -18,6 +18,7 @@ export const DaemonClient = GObject.registerClass({
         this._inputStream = null;
         this._outputStream = null;
         this._reconnectTimeout = null;
+        this._halted = false;
     }
 
     start() {
@@ -74,12 +75,18 @@ export const DaemonClient = GObject.registerClass({
         this._outputStream = null;
     }
 
+    // Stops reconnecting after the daemon closes this connection, for a
+    // refusal that retrying cannot fix.
+    halt() {
+        this._halted = true;
+    }
+
     _getSocketPath() {
         return GLib.get_user_runtime_dir() + '/agents-monitor/daemon.sock';
     }
 
     _scheduleReconnect() {
-        if (this._reconnectTimeout !== null || this._cancellable.is_cancelled())
+        if (this._reconnectTimeout !== null || this._halted || this._cancellable.is_cancelled())
             return;
 
         this._reconnectTimeout = GLib.timeout_add(GLib.PRIORITY_DEFAULT, RECONNECT_DELAY, () => {
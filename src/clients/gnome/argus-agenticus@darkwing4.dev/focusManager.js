@@ -141,7 +141,20 @@ export class FocusManager {
         this.focusWindow(session, agentType);
     }
 
-    returnWorkspace() {
+    returnWorkspace(destination) {
+        if (destination) {
+            this._originalWorkspace = null;
+            const ws = global.workspace_manager.get_workspace_by_index(destination.workspace);
+            if (ws)
+                ws.activate(global.get_current_time());
+            const win = destination.window && global.get_window_actors()
+                .map(a => a.meta_window)
+                .find(w => String(w.get_stable_sequence()) === destination.window);
+            if (win)
+                win.activate(global.get_current_time());
+            return;
+        }
+
         if (this._originalWorkspace === null)
             return;
 
@@ -195,6 +208,17 @@ export class FocusManager {
         sendMessage({ type: 'window_focus', title, agent_type: agentType || '' });
     }
 
+    sendLocation(win, sendMessage) {
+        const msg = {
+            type: 'location',
+            workspace: global.workspace_manager.get_active_workspace_index(),
+            monitor: win ? win.get_monitor() : global.display.get_current_monitor(),
+        };
+        if (win)
+            msg.window = String(win.get_stable_sequence());
+        sendMessage(msg);
+    }
+
     sendWorkspaceForWindow(win, sendMessage) {
         if (!win || !this._windowTracker.isAgentWindow(win))
             return;
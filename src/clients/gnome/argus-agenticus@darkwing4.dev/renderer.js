@@ -110,7 +110,8 @@ export class Renderer {
 
             container.style = groupIndex > 0 ? `margin-left: ${MARGIN_DIFFERENT_GROUP}px;` : '';
 
-            const groupName = groupAgents[0].session.split('#')[0];
+            const first = groupAgents[0];
+            const groupName = first.group_label ?? first.group_key ?? first.session.split('#')[0];
             let label = this._groupLabels.get(groupId);
             if (showLabels) {
                 if (!label) {
@@ -139,6 +140,7 @@ export class Renderer {
                     this._dotWidgets.set(agent.session, widgets);
                 }
 
+                widgets.agent = agent;
                 this._setDotState(widgets.dot, agent.state);
                 this._setDotType(widgets.dot, agent.agent_type);
 
@@ -227,9 +229,11 @@ export class Renderer {
 
         button.set_child(dot);
 
+        const widgets = { button, dot, agent };
+
         button.connect('enter-event', () => {
             dot.set_scale(HOVER_SCALE, HOVER_SCALE);
-            this.showTooltip(button, agent.session);
+            this.showTooltip(button, this._tooltipText(widgets.agent));
         });
 
         button.connect('leave-event', () => {
@@ -241,7 +245,24 @@ export class Renderer {
             callbacks.onDotClicked?.(agent.session);
         });
 
-        return { button, dot };
+        return widgets;
+    }
+
+    _tooltipText(agent) {
+        const lines = [agent.session];
+        if (agent.state_since)
+            lines[0] += ` — ${agent.state} for ${formatDuration(Date.now() - agent.state_since)}`;
+        if (agent.cwd)
+            lines.push(agent.git_branch ? `${agent.cwd} (${agent.git_branch})` : agent.cwd);
+        else if (agent.git_branch)
+            lines.push(agent.git_branch);
+        if (agent.model)
+            lines.push(agent.model);
+        if (agent.parent_session)
+            lines.push(`subagent of ${agent.parent_session}`);
+        else if (agent.aggregate_state && agent.aggregate_state !== agent.state)
+            lines.push(`subagents: ${agent.aggregate_state}`);
+        return lines.join('\n');
     }
 
     _setDotState(dot, state) {
@@ -261,3 +282,13 @@ export class Renderer {
             dot.add_style_class_name(typeInfo.dotClass);
     }
 }
+
+function formatDuration(ms) {
+    const minutes = Math.max(0, Math.floor(ms / 60000));
+    if (minutes < 1)
+        return '<1m';
+    if (minutes < 60)
+        return `${minutes}m`;
+    const hours = Math.floor(minutes / 60);
+    return `${hours}h ${minutes % 60}m`;
+}
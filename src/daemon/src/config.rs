@@ -0,0 +1,483 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::protocol::{AgentState, SessionMetadata};
+use crate::state::StateManager;
+
+const MIN_LINE_LENGTH: usize = 1024;
+const MAX_CHANNEL_CAPACITY: usize = 65_536;
+
+/// Daemon tunables, read from `config.toml`. Every key is optional and
+/// defaults to the value the daemon used before it had a config file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// How long an ended session stays visible before it is removed.
+    pub ended_hide_delay_secs: u64,
+    /// How long a session may sit in `started` before it is considered
+    /// ended, for agent types whose `SessionEnd` is not reliable.
+    pub stale_timeout_secs: u64,
+    pub cleanup_interval_secs: u64,
+    /// How often sessions that reported a `pid` are checked against `/proc`.
+    pub liveness_interval_secs: u64,
+    /// States `focus_next` cycles through, most urgent first.
+    pub focus_priorities: Vec<AgentState>,
+    /// Which sessions `focus_next` considers, relative to the focused window.
+    pub focus_scope: FocusScope,
+    /// How auto-focus picks the next awaiting session.
+    pub auto_focus_policy: AutoFocusPolicy,
+    /// Priority per group key, used by the `weighted` policy.
+    pub project_priorities: HashMap<String, i32>,
+    pub auto_focus_weights: AutoFocusWeights,
+    /// How long auto-focus stays on a session before it may switch again.
+    pub auto_focus_dwell_secs: u64,
+    /// How long auto-focus holds off after focus leaves an agent window for
+    /// another window.
+    pub auto_focus_return_cooldown_secs: u64,
+    /// Most auto-focus switches in any minute; 0 is unlimited.
+    pub auto_focus_max_per_minute: u32,
+    /// Broadcast buffer per client. Only read at startup.
+    pub channel_capacity: usize,
+    /// Longest accepted input line in bytes. Applies to new connections.
+    pub max_line_length: usize,
+    /// Per-agent-type lifecycle overrides, keyed by `agent_type`.
+    pub agents: HashMap<String, AgentPolicyConfig>,
+    /// How sessions are grouped when neither the hook nor a rule names a group.
+    pub group_by: GroupBy,
+    /// Checked in order; the first match sets the group.
+    pub group_rules: Vec<GroupRule>,
+    /// Recurring do-not-disturb periods, in local time.
+    pub quiet_hours: Vec<QuietHours>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    /// The session id up to `#`: the zellij session or repository name the
+    /// hook put there.
+    #[default]
+    SessionId,
+    /// The reported `repository`, falling back to the session id.
+    Repository,
+    /// The reported `cwd`, falling back to the session id.
+    Cwd,
+}
+
+/// A do-not-disturb period that repeats on the listed days. One that ends
+/// at or before its start runs past midnight and belongs to its start day.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuietHours {
+    #[serde(default = "Weekday::all")]
+    pub days: Vec<Weekday>,
+    pub start: ClockTime,
+    pub end: ClockTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, day: Weekday, time: ClockTime) -> bool {
+        if self.start < self.end {
+            self.days.contains(&day) && self.start <= time && time < self.end
+        } else {
+            (self.days.contains(&day) && time >= self.start)
+                || (self.days.contains(&day.prev()) && time < self.end)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Weekday {
+    const ALL: [Weekday; 7] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ];
+
+    fn all() -> Vec<Weekday> {
+        Self::ALL.to_vec()
+    }
+
+    fn prev(self) -> Weekday {
+        Self::ALL[(self as usize + 6) % 7]
+    }
+}
+
+/// Time of day as `"HH:MM"`, held as minutes since midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ClockTime(u16);
+
+impl ClockTime {
+    pub fn new(hour: u16, minute: u16) -> Option<Self> {
+        (hour < 24 && minute < 60).then_some(ClockTime(hour * 60 + minute))
+    }
+}
+
+impl TryFrom<String> for ClockTime {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.split_once(':')
+            .filter(|(h, m)| h.len() <= 2 && m.len() == 2)
+            .and_then(|(h, m)| ClockTime::new(h.parse().ok()?, m.parse().ok()?))
+            .ok_or_else(|| format!("invalid time {:?}, expected \"HH:MM\"", text))
+    }
+}
+
+/// The local weekday and time of day, for `quiet_hours`.
+pub fn local_time() -> (Weekday, ClockTime) {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let now = secs as libc::time_t;
+    // SAFETY: `tm` is plain data, and localtime_r only writes through the
+    // pointers it is given.
+    let tm = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        tm
+    };
+    let day = Weekday::ALL[(tm.tm_wday as usize + 6) % 7];
+    (day, ClockTime((tm.tm_hour * 60 + tm.tm_min) as u16))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FocusScope {
+    #[default]
+    All,
+    /// Sessions on the monitor of the focused window.
+    Monitor,
+    /// Sessions on the workspace of the focused window.
+    Workspace,
+}
+
+impl FocusScope {
+    /// Whether a session at `(monitor, workspace)` is in scope when the user
+    /// is at `here`. Without a known location every session is.
+    pub fn contains(self, here: Option<(u32, u32)>, at: (u32, u32)) -> bool {
+        match (self, here) {
+            (FocusScope::All, _) | (_, None) => true,
+            (FocusScope::Monitor, Some((mon, _))) => at.0 == mon,
+            (FocusScope::Workspace, Some(here)) => at == here,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoFocusPolicy {
+    /// In the order sessions started awaiting.
+    #[default]
+    Fifo,
+    /// Longest time in `awaiting` first.
+    LongestWaiting,
+    /// Sessions on the focused window's monitor first.
+    CurrentMonitor,
+    /// Same monitor first, then the closest workspace number.
+    NearestWorkspace,
+    /// Highest score from `auto_focus_weights`.
+    Weighted,
+}
+
+/// Score terms for the `weighted` policy; the session with the highest sum
+/// is focused first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutoFocusWeights {
+    /// Per point of the session group's `project_priorities` entry.
+    pub project: i64,
+    /// Per whole minute spent awaiting.
+    pub waiting_minute: i64,
+    pub same_monitor: i64,
+    pub same_workspace: i64,
+}
+
+impl Default for AutoFocusWeights {
+    fn default() -> Self {
+        Self { project: 10, waiting_minute: 1, same_monitor: 5, same_workspace: 5 }
+    }
+}
+
+/// One `[[group_rules]]` entry. Every matcher that is set must match.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroupRule {
+    pub session_prefix: Option<String>,
+    pub agent_type: Option<String>,
+    pub cwd_prefix: Option<String>,
+    pub group: String,
+    pub label: Option<String>,
+}
+
+impl GroupRule {
+    pub fn matches(&self, session: &str, agent_type: &str, meta: &SessionMetadata) -> bool {
+        self.session_prefix.as_ref().is_none_or(|p| session.starts_with(p.as_str()))
+            && self.agent_type.as_ref().is_none_or(|t| t == agent_type)
+            && self
+                .cwd_prefix
+                .as_ref()
+                .is_none_or(|p| meta.cwd.as_ref().is_some_and(|cwd| cwd.starts_with(p.as_str())))
+    }
+}
+
+/// One `[agents.<type>]` table. Unset keys fall back to the built-in entry
+/// for that type, then to the top-level values.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgentPolicyConfig {
+    pub stale_timeout_secs: Option<u64>,
+    /// The agent always reports `SessionEnd`, so its sessions never go stale.
+    pub session_end_reliable: Option<bool>,
+    pub ended_hide_delay_secs: Option<u64>,
+    /// Higher is focused first among sessions in the same state.
+    pub focus_priority: Option<i32>,
+}
+
+impl AgentPolicyConfig {
+    fn or(self, fallback: AgentPolicyConfig) -> AgentPolicyConfig {
+        AgentPolicyConfig {
+            stale_timeout_secs: self.stale_timeout_secs.or(fallback.stale_timeout_secs),
+            session_end_reliable: self.session_end_reliable.or(fallback.session_end_reliable),
+            ended_hide_delay_secs: self.ended_hide_delay_secs.or(fallback.ended_hide_delay_secs),
+            focus_priority: self.focus_priority.or(fallback.focus_priority),
+        }
+    }
+}
+
+/// Resolved lifecycle policy for one agent type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AgentPolicy {
+    pub stale_timeout: Duration,
+    pub session_end_reliable: bool,
+    pub ended_hide_delay: Duration,
+    pub focus_priority: i32,
+}
+
+/// Entries shipped with the daemon; a user table only overrides the keys it sets.
+fn builtin_policy(agent_type: &str) -> AgentPolicyConfig {
+    match agent_type {
+        "claude" => AgentPolicyConfig { session_end_reliable: Some(true), ..Default::default() },
+        _ => AgentPolicyConfig::default(),
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            ended_hide_delay_secs: 10,
+            stale_timeout_secs: 30,
+            cleanup_interval_secs: 5,
+            liveness_interval_secs: 5,
+            focus_priorities: vec![AgentState::Awaiting, AgentState::Completed, AgentState::Started],
+            focus_scope: FocusScope::default(),
+            auto_focus_policy: AutoFocusPolicy::default(),
+            project_priorities: HashMap::new(),
+            auto_focus_weights: AutoFocusWeights::default(),
+            auto_focus_dwell_secs: 0,
+            auto_focus_return_cooldown_secs: 0,
+            auto_focus_max_per_minute: 0,
+            channel_capacity: 64,
+            max_line_length: 65_536,
+            agents: HashMap::new(),
+            group_by: GroupBy::default(),
+            group_rules: Vec::new(),
+            quiet_hours: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn ended_hide_delay(&self) -> Duration {
+        Duration::from_secs(self.ended_hide_delay_secs)
+    }
+
+    pub fn stale_timeout(&self) -> Duration {
+        Duration::from_secs(self.stale_timeout_secs)
+    }
+
+    pub fn cleanup_interval(&self) -> Duration {
+        Duration::from_secs(self.cleanup_interval_secs)
+    }
+
+    pub fn liveness_interval(&self) -> Duration {
+        Duration::from_secs(self.liveness_interval_secs)
+    }
+
+    pub fn auto_focus_dwell(&self) -> Duration {
+        Duration::from_secs(self.auto_focus_dwell_secs)
+    }
+
+    pub fn auto_focus_return_cooldown(&self) -> Duration {
+        Duration::from_secs(self.auto_focus_return_cooldown_secs)
+    }
+
+    /// Whether `quiet_hours` covers the given local time.
+    pub fn in_quiet_hours(&self, day: Weekday, time: ClockTime) -> bool {
+        self.quiet_hours.iter().any(|q| q.contains(day, time))
+    }
+
+    pub fn policy(&self, agent_type: &str) -> AgentPolicy {
+        let p = self
+            .agents
+            .get(agent_type)
+            .cloned()
+            .unwrap_or_default()
+            .or(builtin_policy(agent_type));
+        AgentPolicy {
+            stale_timeout: Duration::from_secs(p.stale_timeout_secs.unwrap_or(self.stale_timeout_secs)),
+            session_end_reliable: p.session_end_reliable.unwrap_or(false),
+            ended_hide_delay: Duration::from_secs(p.ended_hide_delay_secs.unwrap_or(self.ended_hide_delay_secs)),
+            focus_priority: p.focus_priority.unwrap_or(0),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let config: Config = toml::from_str(text).map_err(ConfigError::Parse)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.stale_timeout_secs == 0 {
+            return Err(ConfigError::Invalid("stale_timeout_secs must be at least 1".to_string()));
+        }
+        if self.cleanup_interval_secs == 0 {
+            return Err(ConfigError::Invalid("cleanup_interval_secs must be at least 1".to_string()));
+        }
+        if self.liveness_interval_secs == 0 {
+            return Err(ConfigError::Invalid("liveness_interval_secs must be at least 1".to_string()));
+        }
+        if self.focus_priorities.is_empty() {
+            return Err(ConfigError::Invalid("focus_priorities must not be empty".to_string()));
+        }
+        if let Some(dup) = self
+            .focus_priorities
+            .iter()
+            .enumerate()
+            .find(|(i, s)| self.focus_priorities[..*i].contains(s))
+        {
+            return Err(ConfigError::Invalid(format!("focus_priorities lists {:?} twice", dup.1)));
+        }
+        if self.channel_capacity == 0 || self.channel_capacity > MAX_CHANNEL_CAPACITY {
+            return Err(ConfigError::Invalid(format!(
+                "channel_capacity must be between 1 and {}",
+                MAX_CHANNEL_CAPACITY
+            )));
+        }
+        for (agent_type, policy) in &self.agents {
+            if agent_type.is_empty() {
+                return Err(ConfigError::Invalid("agent type names must not be empty".to_string()));
+            }
+            if policy.stale_timeout_secs == Some(0) {
+                return Err(ConfigError::Invalid(format!(
+                    "agents.{}.stale_timeout_secs must be at least 1",
+                    agent_type
+                )));
+            }
+        }
+        for (i, rule) in self.group_rules.iter().enumerate() {
+            if rule.group.is_empty() {
+                return Err(ConfigError::Invalid(format!("group_rules[{}].group must not be empty", i)));
+            }
+            if rule.session_prefix.is_none() && rule.agent_type.is_none() && rule.cwd_prefix.is_none() {
+                return Err(ConfigError::Invalid(format!(
+                    "group_rules[{}] needs session_prefix, agent_type or cwd_prefix",
+                    i
+                )));
+            }
+        }
+        for (i, quiet) in self.quiet_hours.iter().enumerate() {
+            if quiet.start == quiet.end {
+                return Err(ConfigError::Invalid(format!("quiet_hours[{}] starts and ends at the same time", i)));
+            }
+            if quiet.days.is_empty() {
+                return Err(ConfigError::Invalid(format!("quiet_hours[{}].days must not be empty", i)));
+            }
+        }
+        if self.max_line_length < MIN_LINE_LENGTH {
+            return Err(ConfigError::Invalid(format!(
+                "max_line_length must be at least {}",
+                MIN_LINE_LENGTH
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "cannot read config: {}", e),
+            ConfigError::Parse(e) => write!(f, "cannot parse config: {}", e),
+            ConfigError::Invalid(msg) => write!(f, "invalid config: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+pub fn default_path() -> PathBuf {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .unwrap_or_else(|| PathBuf::from("/tmp"));
+    base.join("argus-agenticus").join("config.toml")
+}
+
+/// Reads and validates the config at `path`; a missing file means defaults.
+pub fn load(path: &Path) -> Result<Config, ConfigError> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Config::parse(&text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(ConfigError::Io(e)),
+    }
+}
+
+/// Re-reads the config into `state`. An invalid file is reported and the
+/// running config is kept. Returns whether sessions were regrouped.
+pub async fn reload(path: &Path, state: &Mutex<StateManager>) -> bool {
+    let config = match load(path) {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("Keeping current config, {:?}: {}", path, e);
+            return false;
+        }
+    };
+
+    let mut s = state.lock().await;
+    if *s.config() == config {
+        return false;
+    }
+    if s.config().channel_capacity != config.channel_capacity {
+        warn!("channel_capacity change takes effect after a restart");
+    }
+    info!("Reloaded config from {:?}", path);
+    s.set_config(config)
+}
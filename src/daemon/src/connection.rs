@@ -1,4 +1,4 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
 
 use bytes::BytesMut;
@@ -7,13 +7,45 @@ use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::UnixStream;
 use tokio::sync::{broadcast, mpsc, Mutex, Notify};
 use tokio_util::codec::{Decoder, LinesCodec, LinesCodecError};
-use tracing::warn;
+use tracing::{info, warn};
 
 use crate::handler;
-use crate::protocol::{IncomingMessage, OutgoingMessage};
+use crate::protocol::{
+    AgentInfo, Envelope, ErrorCode, OutgoingMessage, ProtocolError, Reply, RequestId, Topic,
+};
+use crate::publish;
 use crate::state::{AutoFocusEvent, StateManager};
 
-const MAX_LINE_LENGTH: usize = 65_536;
+#[derive(Default)]
+struct Peer {
+    topics: AtomicU8,
+    explicit_topics: AtomicBool,
+    /// The client listed `render_delta` among its `hello` capabilities, so
+    /// its default topics take deltas instead of full renders.
+    prefers_delta: AtomicBool,
+    lagged: AtomicU64,
+}
+
+impl Peer {
+    fn is_subscribed(&self, topic: Topic) -> bool {
+        self.topics.load(Ordering::Acquire) & topic.bit() != 0
+    }
+
+    fn subscribe(&self, topics: &[Topic]) -> u8 {
+        let bits = topics.iter().fold(0, |acc, t| acc | t.bit());
+        let prev = self.topics.fetch_or(bits, Ordering::AcqRel);
+        bits & !prev
+    }
+
+    fn unsubscribe(&self, topics: &[Topic]) {
+        let bits = topics.iter().fold(0, |acc, t| acc | t.bit());
+        self.topics.fetch_and(!bits, Ordering::AcqRel);
+    }
+
+    fn topics(&self) -> Vec<Topic> {
+        Topic::ALL.iter().copied().filter(|t| self.is_subscribed(*t)).collect()
+    }
+}
 
 pub struct Connection {
     state: Arc<Mutex<StateManager>>,
@@ -32,25 +64,48 @@ impl Connection {
 
     pub async fn run(&self, stream: UnixStream) -> std::io::Result<()> {
         let (reader, writer) = stream.into_split();
-        let is_extension = AtomicBool::new(false);
+        let peer = Peer::default();
         let (reply_tx, reply_rx) = mpsc::channel(16);
         let broadcast_rx = self.broadcast_tx.subscribe();
 
-        tokio::select! {
-            r = self.read_loop(reader, &reply_tx, &is_extension) => r?,
-            _ = Self::write_loop(writer, reply_rx, broadcast_rx, &is_extension) => {},
+        let write = self.write_loop(writer, reply_rx, broadcast_rx, &peer);
+        tokio::pin!(write);
+
+        let read = tokio::select! {
+            r = self.read_loop(reader, reply_tx, &peer) => Some(r),
+            _ = &mut write => None,
+        };
+
+        let result = match read {
+            Some(r) => {
+                if r.is_ok() {
+                    write.await;
+                }
+                r
+            }
+            None => Ok(()),
+        };
+
+        if peer.is_subscribed(Topic::Lifecycle) {
+            self.state.lock().await.track_lifecycle_subscriber(false);
         }
 
-        Ok(())
+        let lagged = peer.lagged.load(Ordering::Relaxed);
+        if lagged > 0 {
+            info!("Connection closed after lagging {} times", lagged);
+        }
+
+        result
     }
 
     async fn read_loop(
         &self,
         mut reader: OwnedReadHalf,
-        reply_tx: &mpsc::Sender<OutgoingMessage>,
-        is_extension: &AtomicBool,
+        reply_tx: mpsc::Sender<Reply>,
+        peer: &Peer,
     ) -> std::io::Result<()> {
-        let mut codec = LinesCodec::new_with_max_length(MAX_LINE_LENGTH);
+        let max_line_length = self.state.lock().await.config().max_line_length;
+        let mut codec = LinesCodec::new_with_max_length(max_line_length);
         let mut buf = BytesMut::with_capacity(4096);
 
         loop {
@@ -58,12 +113,19 @@ impl Connection {
                 Ok(Some(line)) => {
                     let trimmed = line.trim();
                     if !trimmed.is_empty() {
-                        match serde_json::from_str::<IncomingMessage>(trimmed) {
-                            Ok(msg) => {
-                                let effects = handler::process(msg, &self.state).await;
-                                self.apply(effects, reply_tx, is_extension).await;
+                        match Envelope::parse(trimmed) {
+                            Ok(Envelope { id, message }) => {
+                                let effects = handler::process(message, &self.state).await;
+                                let disconnect = effects.disconnect;
+                                self.apply(effects, id, &reply_tx, peer).await;
+                                if disconnect {
+                                    break;
+                                }
+                            }
+                            Err(e) => {
+                                warn!("Rejected message ({:?}): {} - {}", e.code, trimmed, e.message);
+                                let _ = reply_tx.send(e.into()).await;
                             }
-                            Err(e) => warn!("Invalid JSON: {} - {}", trimmed, e),
                         }
                     }
                     continue;
@@ -71,6 +133,12 @@ impl Connection {
                 Ok(None) => {}
                 Err(LinesCodecError::MaxLineLengthExceeded) => {
                     warn!("Line too long, dropping");
+                    let e = ProtocolError::new(
+                        ErrorCode::LineTooLong,
+                        None,
+                        format!("line exceeds {} bytes", max_line_length),
+                    );
+                    let _ = reply_tx.send(e.into()).await;
                     continue;
                 }
                 Err(LinesCodecError::Io(e)) => return Err(e),
@@ -85,52 +153,142 @@ impl Connection {
     async fn apply(
         &self,
         effects: handler::Effects,
-        reply_tx: &mpsc::Sender<OutgoingMessage>,
-        is_extension: &AtomicBool,
+        id: Option<RequestId>,
+        reply_tx: &mpsc::Sender<Reply>,
+        peer: &Peer,
     ) {
-        if effects.mark_extension {
-            is_extension.store(true, Ordering::Release);
+        let reply = |message| Reply { id: id.clone(), message };
+        let mut replied = false;
+
+        if let Some(client) = effects.client {
+            info!(
+                "Client connected: {:?} (protocol v{}, capabilities {:?})",
+                client.kind, client.protocol_version, client.capabilities
+            );
+            let delta = client.capabilities.iter().any(|c| c == "render_delta");
+            peer.prefers_delta.store(delta, Ordering::Release);
+        }
+
+        if effects.mark_extension && !peer.explicit_topics.load(Ordering::Acquire) {
+            if peer.prefers_delta.load(Ordering::Acquire) {
+                let topics: Vec<Topic> = Topic::EXTENSION
+                    .iter()
+                    .map(|&t| if t == Topic::Render { Topic::RenderDelta } else { t })
+                    .collect();
+                peer.subscribe(&topics);
+            } else {
+                peer.subscribe(Topic::EXTENSION);
+            }
         }
 
-        if let Some(reply) = effects.reply {
-            let _ = reply_tx.send(reply).await;
+        for message in effects.replies {
+            if matches!(message, OutgoingMessage::Focus { .. }) && !peer.is_subscribed(Topic::Focus) {
+                let _ = self.broadcast_tx.send(message.clone());
+            }
+            let _ = reply_tx.send(reply(message)).await;
+            replied = true;
         }
 
+        if id.is_some() {
+            for message in effects.rejections {
+                let _ = reply_tx.send(reply(message)).await;
+                replied = true;
+            }
+        }
+
+        if effects.subscribe.is_some() || effects.unsubscribe.is_some() {
+            peer.explicit_topics.store(true, Ordering::Release);
+            let had_lifecycle = peer.is_subscribed(Topic::Lifecycle);
+            let added = peer.subscribe(effects.subscribe.as_deref().unwrap_or_default());
+            peer.unsubscribe(effects.unsubscribe.as_deref().unwrap_or_default());
+            let lifecycle = peer.is_subscribed(Topic::Lifecycle);
+            if lifecycle != had_lifecycle {
+                self.state.lock().await.track_lifecycle_subscriber(lifecycle);
+            }
+            let _ = reply_tx.send(reply(OutgoingMessage::Subscribed { topics: peer.topics() })).await;
+            replied = true;
+
+            if added & (Topic::Render.bit() | Topic::RenderDelta.bit()) != 0 {
+                let snapshot = publish::snapshot(&*self.state.lock().await);
+                let _ = reply_tx.send(reply(snapshot)).await;
+            }
+        }
+
+        if !replied && id.is_some() {
+            let _ = reply_tx.send(reply(OutgoingMessage::Ack)).await;
+        }
+
+        let mut s = self.state.lock().await;
         match effects.auto_focus {
             AutoFocusEvent::Trigger => self.auto_focus_notify.notify_one(),
-            AutoFocusEvent::QueueEmpty => {
-                let _ = self.broadcast_tx.send(OutgoingMessage::ReturnWorkspace);
-            }
+            AutoFocusEvent::QueueEmpty => publish::return_workspace(&mut s, &self.broadcast_tx),
             AutoFocusEvent::None => {}
         }
-
+        publish::changes(&mut s, &self.broadcast_tx);
         if effects.broadcast_render {
-            let render = {
-                let s = self.state.lock().await;
-                OutgoingMessage::Render { agents: s.get_render_data() }
-            };
-            let _ = self.broadcast_tx.send(render);
+            publish::render(&mut s, &self.broadcast_tx);
         }
     }
 
     async fn write_loop(
+        &self,
         mut writer: OwnedWriteHalf,
-        mut reply_rx: mpsc::Receiver<OutgoingMessage>,
+        mut reply_rx: mpsc::Receiver<Reply>,
         mut broadcast_rx: broadcast::Receiver<OutgoingMessage>,
-        is_extension: &AtomicBool,
+        peer: &Peer,
     ) {
+        let mut baseline: Option<(u64, Vec<AgentInfo>)> = None;
+
         loop {
-            let msg = tokio::select! {
-                Some(msg) = reply_rx.recv() => msg,
-                result = broadcast_rx.recv() => match result {
-                    Ok(msg) if is_extension.load(Ordering::Acquire) => msg,
-                    Ok(_) => continue,
-                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
-                    Err(broadcast::error::RecvError::Closed) => break,
+            let out = tokio::select! {
+                reply = reply_rx.recv() => match reply {
+                    Some(reply) => reply,
+                    None => break,
                 },
+                result = broadcast_rx.recv() => {
+                    let message = match result {
+                        Ok(OutgoingMessage::Render { seq, agents }) => {
+                            if peer.is_subscribed(Topic::Render) {
+                                OutgoingMessage::Render { seq, agents }
+                            } else if !peer.is_subscribed(Topic::RenderDelta) {
+                                continue;
+                            } else if let Some((base_seq, base)) = &baseline {
+                                match publish::delta(seq, *base_seq, base, &agents) {
+                                    Some(delta) => {
+                                        baseline = Some((seq, agents));
+                                        delta
+                                    }
+                                    None => continue,
+                                }
+                            } else {
+                                OutgoingMessage::Render { seq, agents }
+                            }
+                        }
+                        Ok(msg) if msg.topic().is_some_and(|t| peer.is_subscribed(t)) => msg,
+                        Ok(_) => continue,
+                        Err(broadcast::error::RecvError::Lagged(_))
+                            if peer.topics.load(Ordering::Acquire) == 0 => continue,
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            let times = peer.lagged.fetch_add(1, Ordering::Relaxed) + 1;
+                            warn!("Client lagged, {} messages skipped ({} times)", skipped, times);
+                            if !peer.is_subscribed(Topic::Render) && !peer.is_subscribed(Topic::RenderDelta) {
+                                continue;
+                            }
+                            publish::snapshot(&*self.state.lock().await)
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    };
+                    Reply { id: None, message }
+                }
             };
 
-            let json = serde_json::to_string(&msg).expect("serialize OutgoingMessage") + "\n";
+            if let OutgoingMessage::Render { seq, agents } = &out.message {
+                if peer.is_subscribed(Topic::RenderDelta) {
+                    baseline = Some((*seq, agents.clone()));
+                }
+            }
+
+            let json = serde_json::to_string(&out).expect("serialize OutgoingMessage") + "\n";
             if writer.write_all(json.as_bytes()).await.is_err() {
                 break;
             }
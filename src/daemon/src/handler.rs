@@ -1,147 +1,365 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use tokio::sync::Mutex;
 use tracing::debug;
 
-use crate::protocol::IncomingMessage;
-use crate::protocol::OutgoingMessage;
+use crate::config;
+use crate::protocol::{
+    ClientKind, ErrorCode, IncomingMessage, Location, OutgoingMessage, Topic, DAEMON_VERSION, FEATURES,
+    MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+use crate::publish;
 use crate::state::{AutoFocusEvent, StateManager};
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub kind: ClientKind,
+    pub protocol_version: u32,
+    pub capabilities: Vec<String>,
+}
+
+#[derive(Default)]
 pub struct Effects {
-    pub reply: Option<OutgoingMessage>,
+    pub replies: Vec<OutgoingMessage>,
+    /// Errors for dropped hook updates. Only sent when the message carries
+    /// an `id`, so fire-and-forget hooks never get a line back.
+    pub rejections: Vec<OutgoingMessage>,
     pub auto_focus: AutoFocusEvent,
     pub mark_extension: bool,
     pub broadcast_render: bool,
+    pub client: Option<ClientInfo>,
+    pub disconnect: bool,
+    pub subscribe: Option<Vec<Topic>>,
+    pub unsubscribe: Option<Vec<Topic>>,
+}
+
+impl Effects {
+    /// Folds the effects of a later message into this one, as if both had
+    /// been applied in order.
+    pub fn merge(&mut self, next: Effects) {
+        self.replies.extend(next.replies);
+        self.rejections.extend(next.rejections);
+        if next.auto_focus != AutoFocusEvent::None {
+            self.auto_focus = next.auto_focus;
+        }
+        self.mark_extension |= next.mark_extension;
+        self.broadcast_render |= next.broadcast_render;
+        if next.client.is_some() {
+            self.client = next.client;
+        }
+        self.disconnect |= next.disconnect;
+        if let Some(topics) = next.subscribe {
+            if let Some(removed) = &mut self.unsubscribe {
+                removed.retain(|t| !topics.contains(t));
+            }
+            self.subscribe.get_or_insert_with(Vec::new).extend(topics);
+        }
+        if let Some(topics) = next.unsubscribe {
+            if let Some(added) = &mut self.subscribe {
+                added.retain(|t| !topics.contains(t));
+            }
+            self.unsubscribe.get_or_insert_with(Vec::new).extend(topics);
+        }
+    }
 }
 
 pub async fn process(
     msg: IncomingMessage,
     state: &Arc<Mutex<StateManager>>,
 ) -> Effects {
+    let mut s = state.lock().await;
+    process_locked(msg, &mut s)
+}
+
+/// Applies one message to already-locked state. A batch is applied in order
+/// and its effects are merged, so it yields at most one render broadcast and
+/// one auto-focus event; a message that disconnects stops the batch.
+pub fn process_locked(msg: IncomingMessage, s: &mut StateManager) -> Effects {
     match msg {
-        IncomingMessage::State { session, state: agent_state, tool, agent_type } => {
-            debug!("State: {} -> {:?} ({}) [{}]", session, agent_state, tool, agent_type);
+        IncomingMessage::Hello { protocol_version, client, capabilities } => {
+            debug!("Hello: {:?} v{} {:?}", client, protocol_version, capabilities);
+            if protocol_version < MIN_PROTOCOL_VERSION {
+                return Effects {
+                    replies: vec![OutgoingMessage::Refused {
+                        reason: format!(
+                            "protocol version {} is no longer supported, minimum is {}",
+                            protocol_version, MIN_PROTOCOL_VERSION
+                        ),
+                        protocol_version: PROTOCOL_VERSION,
+                        min_protocol_version: MIN_PROTOCOL_VERSION,
+                    }],
+                    disconnect: true,
+                    ..Effects::default()
+                };
+            }
+            Effects {
+                replies: vec![OutgoingMessage::Welcome {
+                    protocol_version: PROTOCOL_VERSION,
+                    min_protocol_version: MIN_PROTOCOL_VERSION,
+                    daemon_version: DAEMON_VERSION.to_string(),
+                    features: FEATURES.iter().map(|f| f.to_string()).collect(),
+                }],
+                mark_extension: client == ClientKind::Extension,
+                client: Some(ClientInfo { kind: client, protocol_version, capabilities }),
+                ..Effects::default()
+            }
+        }
+
+        IncomingMessage::State {
+            session,
+            state: agent_state,
+            tool,
+            agent_type,
+            event,
+            seq,
+            parent_session,
+            group,
+            group_label,
+            meta,
+        } => {
+            debug!("State: {} -> {:?} ({}) [{}] {:?} {:?}", session, agent_state, tool, agent_type, event, meta);
             let agent_type: Arc<str> = agent_type.into();
-            let mut s = state.lock().await;
-            let event = s.update_state(session, agent_state, tool, agent_type);
+            let auto_focus = match s.apply_hook_update(session.clone(), agent_state, tool, agent_type, event, seq) {
+                Ok(auto_focus) => auto_focus,
+                Err(rejection) => {
+                    debug!("Dropping {:?} for {}: {:?}", agent_state, session, rejection);
+                    return Effects {
+                        rejections: vec![OutgoingMessage::Error {
+                            code: ErrorCode::Rejected,
+                            message_type: Some("state".to_string()),
+                            message: rejection.to_string(),
+                        }],
+                        ..Effects::default()
+                    };
+                }
+            };
+            s.update_metadata(&session, *meta);
+            if let Some(parent) = parent_session {
+                s.set_parent(&session, parent);
+            }
+            if group.is_some() || group_label.is_some() {
+                s.set_group(&session, group, group_label);
+            }
             Effects {
-                reply: None,
-                auto_focus: event,
-                mark_extension: false,
+                auto_focus,
                 broadcast_render: true,
+                ..Effects::default()
             }
         }
 
         IncomingMessage::WindowFocus { title, agent_type } => {
             debug!("Window focus: {} [{}]", title, agent_type);
-            let mut s = state.lock().await;
             let at = if agent_type.is_empty() { None } else { Some(agent_type.as_str()) };
             s.update_window_focus(&title, at);
             Effects {
-                reply: None,
-                auto_focus: AutoFocusEvent::None,
                 mark_extension: true,
                 broadcast_render: true,
+                ..Effects::default()
             }
         }
 
         IncomingMessage::SessionWorkspace { session, workspace, monitor } => {
             debug!("Session workspace: {} -> ws:{} mon:{}", session, workspace, monitor);
-            let mut s = state.lock().await;
             s.update_workspace(&session, workspace, monitor);
             Effects {
-                reply: None,
-                auto_focus: AutoFocusEvent::None,
-                mark_extension: false,
                 broadcast_render: true,
+                ..Effects::default()
             }
         }
 
+        IncomingMessage::Location { workspace, monitor, window } => {
+            debug!("Location: ws:{} mon:{} window:{:?}", workspace, monitor, window);
+            s.set_location(Location { workspace, monitor, window });
+            Effects::default()
+        }
+
         IncomingMessage::Click { session } => {
             debug!("Click: {}", session);
-            let s = state.lock().await;
+            let session = s.focus_target(&session);
             let agent_type = s.get_agent_type(&session);
             Effects {
-                reply: Some(OutgoingMessage::Focus { session, agent_type }),
-                auto_focus: AutoFocusEvent::None,
-                mark_extension: false,
-                broadcast_render: false,
+                replies: vec![OutgoingMessage::Focus { session, agent_type }],
+                ..Effects::default()
             }
         }
 
         IncomingMessage::FocusNext => {
             debug!("Focus next");
-            let mut s = state.lock().await;
-            let reply = s.focus_next().map(|session| {
-                let agent_type = s.get_agent_type(&session);
-                OutgoingMessage::Focus { session, agent_type }
-            });
-            Effects {
-                reply,
-                auto_focus: AutoFocusEvent::None,
-                mark_extension: false,
-                broadcast_render: false,
-            }
+            let session = s.focus_next();
+            focus_reply(s, session)
+        }
+
+        IncomingMessage::FocusPrev => {
+            debug!("Focus prev");
+            let session = s.focus_prev();
+            focus_reply(s, session)
+        }
+
+        IncomingMessage::FocusNextInState { state: agent_state } => {
+            debug!("Focus next in state: {:?}", agent_state);
+            let session = s.focus_next_in_state(agent_state);
+            focus_reply(s, session)
+        }
+
+        IncomingMessage::FocusIndex { n } => {
+            debug!("Focus index: {}", n);
+            focus_reply(s, s.focus_index(n))
+        }
+
+        IncomingMessage::FocusGroup { group } => {
+            debug!("Focus group: {}", group);
+            focus_reply(s, s.focus_group(&group))
+        }
+
+        IncomingMessage::FocusLast => {
+            debug!("Focus last");
+            focus_reply(s, s.focus_last())
         }
 
         IncomingMessage::IdleStatus { idle } => {
             debug!("Idle status: {}", idle);
-            let mut s = state.lock().await;
             s.set_idle(idle);
             Effects {
-                reply: None,
                 auto_focus: AutoFocusEvent::Trigger,
                 mark_extension: true,
-                broadcast_render: false,
+                ..Effects::default()
+            }
+        }
+
+        IncomingMessage::Dnd { enabled, duration_secs } => {
+            debug!("DND: enabled={}, duration={:?}s", enabled, duration_secs);
+            s.set_dnd(enabled, duration_secs.map(Duration::from_secs));
+            let (day, time) = config::local_time();
+            s.refresh_dnd(day, time);
+            Effects {
+                auto_focus: AutoFocusEvent::Trigger,
+                ..Effects::default()
             }
         }
 
         IncomingMessage::ClearAgents => {
             debug!("Clear agents");
-            let mut s = state.lock().await;
             s.clear_all();
             Effects {
-                reply: None,
-                auto_focus: AutoFocusEvent::None,
-                mark_extension: false,
                 broadcast_render: true,
+                auto_focus: s.finish_auto_focus_run(),
+                ..Effects::default()
             }
         }
 
         IncomingMessage::MarkAllStarted => {
             debug!("Mark all started");
-            let mut s = state.lock().await;
             s.mark_all_started();
             Effects {
-                reply: None,
-                auto_focus: AutoFocusEvent::None,
-                mark_extension: false,
                 broadcast_render: true,
+                auto_focus: s.finish_auto_focus_run(),
+                ..Effects::default()
             }
         }
 
         IncomingMessage::AutoFocusConfig { enabled, focus_delay_ms } => {
             debug!("Auto-focus config: enabled={}, delay={}ms", enabled, focus_delay_ms);
-            let mut s = state.lock().await;
             s.set_auto_focus_config(enabled, focus_delay_ms);
             Effects {
-                reply: None,
                 auto_focus: AutoFocusEvent::Trigger,
                 mark_extension: true,
-                broadcast_render: false,
+                ..Effects::default()
+            }
+        }
+
+        IncomingMessage::ListSessions { state: filter } => {
+            debug!("List sessions: {:?}", filter);
+            let sessions = s
+                .get_session_snapshots()
+                .into_iter()
+                .filter(|snap| filter.is_none_or(|f| snap.agent.state == f))
+                .collect();
+            Effects {
+                replies: vec![OutgoingMessage::Sessions { sessions }],
+                ..Effects::default()
+            }
+        }
+
+        IncomingMessage::GetSession { session } => {
+            debug!("Get session: {}", session);
+            let snapshot = s
+                .get_session_snapshots()
+                .into_iter()
+                .find(|snap| snap.agent.session == session)
+                .map(Box::new);
+            Effects {
+                replies: vec![OutgoingMessage::Session { session, snapshot }],
+                ..Effects::default()
+            }
+        }
+
+        IncomingMessage::SessionHistory { session } => {
+            debug!("Session history: {}", session);
+            let transitions = s.get_history(&session);
+            Effects {
+                replies: vec![OutgoingMessage::History { session, transitions }],
+                ..Effects::default()
             }
         }
 
         IncomingMessage::WindowClosed { session } => {
             debug!("Window closed: {}", session);
-            let mut s = state.lock().await;
             s.remove_session(&session);
             Effects {
-                reply: None,
-                auto_focus: AutoFocusEvent::None,
-                mark_extension: false,
                 broadcast_render: true,
+                auto_focus: s.finish_auto_focus_run(),
+                ..Effects::default()
+            }
+        }
+
+        IncomingMessage::Subscribe { topics } => {
+            debug!("Subscribe: {:?}", topics);
+            Effects {
+                subscribe: Some(topics),
+                ..Effects::default()
             }
         }
+
+        IncomingMessage::Unsubscribe { topics } => {
+            debug!("Unsubscribe: {:?}", topics);
+            Effects {
+                unsubscribe: Some(topics),
+                ..Effects::default()
+            }
+        }
+
+        IncomingMessage::Batch { messages } => {
+            debug!("Batch: {} messages", messages.len());
+            let mut effects = Effects::default();
+            for msg in messages {
+                effects.merge(process_locked(msg, s));
+                if effects.disconnect {
+                    break;
+                }
+            }
+            effects
+        }
+
+        IncomingMessage::Resync => {
+            debug!("Resync");
+            Effects {
+                replies: vec![publish::snapshot(s)],
+                ..Effects::default()
+            }
+        }
+    }
+}
+
+/// Replies with a `focus` for `session`, or nothing when there is no
+/// session to go to.
+fn focus_reply(s: &StateManager, session: Option<String>) -> Effects {
+    let reply = session.map(|session| {
+        let agent_type = s.get_agent_type(&session);
+        OutgoingMessage::Focus { session, agent_type }
+    });
+    Effects {
+        replies: reply.into_iter().collect(),
+        ..Effects::default()
     }
 }
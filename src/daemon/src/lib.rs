@@ -1,7 +1,10 @@
 pub mod alloc_tracker;
+pub mod config;
 pub mod connection;
 pub mod handler;
+pub mod persist;
 pub mod protocol;
+pub mod publish;
 pub mod socket;
 pub mod state;
 pub mod tasks;
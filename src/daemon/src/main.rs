@@ -1,20 +1,38 @@
-mod connection;
-mod handler;
-mod protocol;
-mod socket;
-mod state;
-mod tasks;
-
 use std::env;
 use std::path::PathBuf;
 
-use tracing::info;
+use tracing::{error, info};
 use tracing_subscriber::EnvFilter;
 
-use crate::socket::SocketServer;
+use argus_agenticus::{config, persist, protocol};
+use argus_agenticus::socket::SocketServer;
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
+    match env::args().nth(1).as_deref() {
+        None => {}
+        Some("check-config") => {
+            let path = config::default_path();
+            match config::load(&path) {
+                Ok(_) => println!("{:?}: ok", path),
+                Err(e) => {
+                    eprintln!("{:?}: {}", path, e);
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        Some("schema") => {
+            let schema = serde_json::to_string_pretty(&protocol::schema()).expect("serialize schema");
+            println!("{}", schema);
+            return Ok(());
+        }
+        Some(other) => {
+            eprintln!("unknown command '{}' (expected: schema, check-config)", other);
+            std::process::exit(2);
+        }
+    }
+
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env().add_directive("info".parse().unwrap()))
         .init();
@@ -22,7 +40,18 @@ async fn main() -> std::io::Result<()> {
     let socket_path = get_socket_path();
     info!("argus-agenticus starting...");
 
-    let server = SocketServer::new(socket_path);
+    let config_path = config::default_path();
+    let config = match config::load(&config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{:?}: {}", config_path, e);
+            std::process::exit(1);
+        }
+    };
+
+    let server = SocketServer::with_config(socket_path, config)
+        .watch_config(config_path)
+        .with_state_file(persist::default_path());
     server.run().await
 }
 
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use crate::protocol::{AgentState, SessionMetadata, Transition};
+use crate::state::StateManager;
+
+pub const SNAPSHOT_VERSION: u32 = 1;
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// On-disk form of `StateManager`. Times are Unix milliseconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub saved_at: u64,
+    pub sessions: Vec<PersistedSession>,
+    pub workspaces: HashMap<String, (u32, u32)>,
+    pub awaiting_queue: Vec<String>,
+    pub auto_focus_enabled: bool,
+    pub focus_delay_ms: u64,
+    /// Manual do-not-disturb, so a restart mid-meeting keeps it on.
+    #[serde(default)]
+    pub dnd_manual: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dnd_until: Option<u64>,
+    /// Quiet hours were ended early by turning do-not-disturb off.
+    #[serde(default)]
+    pub quiet_overridden: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PersistedSession {
+    pub session: String,
+    pub state: AgentState,
+    pub tool: String,
+    pub agent_type: String,
+    pub state_since: u64,
+    pub last_activity_at: u64,
+    #[serde(default)]
+    pub meta: SessionMetadata,
+    #[serde(default)]
+    pub history: Vec<Transition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seq: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_session: Option<String>,
+    /// Group and label the hook set explicitly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_label: Option<String>,
+}
+
+pub fn default_path() -> PathBuf {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/state")))
+        .unwrap_or_else(|| PathBuf::from("/tmp"));
+    base.join("argus-agenticus").join("state.json")
+}
+
+/// Loads a snapshot, returning `None` when there is nothing usable: no file,
+/// an unreadable or foreign-version file, or one written before the last boot.
+pub async fn load(path: &Path) -> Option<Snapshot> {
+    let data = match tokio::fs::read(path).await {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!("Cannot read state snapshot {:?}: {}", path, e);
+            return None;
+        }
+    };
+    let snapshot: Snapshot = match serde_json::from_slice(&data) {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("Ignoring malformed state snapshot {:?}: {}", path, e);
+            return None;
+        }
+    };
+    if snapshot.version != SNAPSHOT_VERSION {
+        info!("Ignoring state snapshot version {}", snapshot.version);
+        return None;
+    }
+    if boot_time_millis().is_some_and(|boot| snapshot.saved_at < boot) {
+        info!("Ignoring state snapshot from before the last boot");
+        return None;
+    }
+    Some(snapshot)
+}
+
+pub async fn save(path: &Path, snapshot: &Snapshot) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    let json = serde_json::to_vec(snapshot).expect("serialize Snapshot");
+    tokio::fs::write(&tmp, json).await?;
+    tokio::fs::rename(&tmp, path).await
+}
+
+/// Writes the state to `path` whenever it has changed since the last write.
+pub fn spawn_saver(state: Arc<Mutex<StateManager>>, path: PathBuf) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SAVE_INTERVAL);
+        let mut last: Option<Snapshot> = None;
+        loop {
+            interval.tick().await;
+
+            let snapshot = state.lock().await.to_snapshot();
+            if last.as_ref().is_some_and(|l| same_content(l, &snapshot)) {
+                continue;
+            }
+            match save(&path, &snapshot).await {
+                Ok(()) => debug!("Saved state snapshot ({} sessions)", snapshot.sessions.len()),
+                Err(e) => warn!("Cannot write state snapshot {:?}: {}", path, e),
+            }
+            last = Some(snapshot);
+        }
+    });
+}
+
+/// `last_activity_at` drifts by a millisecond between snapshots, and every
+/// real activity also appends to the history, so it is left out.
+fn same_content(a: &Snapshot, b: &Snapshot) -> bool {
+    a.workspaces == b.workspaces
+        && a.awaiting_queue == b.awaiting_queue
+        && a.auto_focus_enabled == b.auto_focus_enabled
+        && a.focus_delay_ms == b.focus_delay_ms
+        && (a.dnd_manual, a.dnd_until, a.quiet_overridden) == (b.dnd_manual, b.dnd_until, b.quiet_overridden)
+        && a.sessions.len() == b.sessions.len()
+        && a.sessions.iter().zip(&b.sessions).all(|(x, y)| {
+            PersistedSession { last_activity_at: y.last_activity_at, ..x.clone() } == *y
+        })
+}
+
+fn boot_time_millis() -> Option<u64> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    let secs: u64 = stat.lines().find_map(|l| l.strip_prefix("btime "))?.trim().parse().ok()?;
+    Some(secs * 1000)
+}
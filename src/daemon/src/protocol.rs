@@ -1,8 +1,16 @@
 use std::sync::Arc;
 
+use schemars::gen::SchemaSettings;
+use schemars::schema::{Metadata, RootSchema, SchemaObject, SubschemaValidation};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+pub const DAEMON_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const FEATURES: &[&str] = &["auto_focus", "query", "errors", "subscriptions", "render_delta", "request_ids", "batch", "metadata", "history", "liveness", "ordering", "subagents", "groups", "return_destination", "navigation", "dnd"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum AgentState {
     Started,
@@ -13,15 +21,123 @@ pub enum AgentState {
     Ended,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl AgentState {
+    /// Transition table for hook-reported updates. An ended session only
+    /// comes back through a new `SessionStart` or prompt; anything else
+    /// after `SessionEnd` is a straggler from the old run.
+    pub fn accepts(self, next: AgentState) -> bool {
+        use AgentState::*;
+        match (self, next) {
+            (Ended, Started | Processing) => true,
+            (Ended, _) => false,
+            (Started | Awaiting | Working | Processing | Completed, _) => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientKind {
+    Hook,
+    Extension,
+    Cli,
+    Dashboard,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Topic {
+    Render,
+    RenderDelta,
+    Focus,
+    AutoFocus,
+    ReturnWorkspace,
+    Lifecycle,
+    Dnd,
+}
+
+impl Topic {
+    pub const ALL: &'static [Topic] = &[
+        Topic::Render,
+        Topic::RenderDelta,
+        Topic::Focus,
+        Topic::AutoFocus,
+        Topic::ReturnWorkspace,
+        Topic::Lifecycle,
+        Topic::Dnd,
+    ];
+
+    pub const EXTENSION: &'static [Topic] = &[
+        Topic::Render,
+        Topic::Focus,
+        Topic::AutoFocus,
+        Topic::ReturnWorkspace,
+        Topic::Dnd,
+    ];
+
+    pub fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LifecycleEvent {
+    Added,
+    Changed,
+    Removed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DndReason {
+    Manual,
+    QuietHours,
+}
+
+/// Net change of one session over a do-not-disturb period; `None` means
+/// the session did not exist.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DndChange {
+    pub session: String,
+    pub previous: Option<AgentState>,
+    pub state: Option<AgentState>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum IncomingMessage {
+    Hello {
+        protocol_version: u32,
+        client: ClientKind,
+        #[serde(default)]
+        capabilities: Vec<String>,
+    },
     State {
         session: String,
         state: AgentState,
         tool: String,
         #[serde(default = "default_agent_type")]
         agent_type: String,
+        /// Hook event that produced this update, kept in the session history.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        event: Option<String>,
+        /// Ordering key that increases per session (the bundled hook sends
+        /// Unix microseconds). Updates older than the last applied one are dropped.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        seq: Option<u64>,
+        /// Session that spawned this one, for subagents.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parent_session: Option<String>,
+        /// Groups the session explicitly, overriding config rules.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        group: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        group_label: Option<String>,
+        #[serde(flatten)]
+        meta: Box<SessionMetadata>,
     },
     WindowFocus {
         title: String,
@@ -34,10 +150,35 @@ pub enum IncomingMessage {
         #[serde(default)]
         monitor: u32,
     },
+    /// Where the user currently is. The last report before an auto-focus run
+    /// starts becomes the destination of its `return_workspace`.
+    Location {
+        workspace: u32,
+        #[serde(default)]
+        monitor: u32,
+        /// Client-defined id of the focused window.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        window: Option<String>,
+    },
     Click {
         session: String,
     },
     FocusNext,
+    FocusPrev,
+    /// Cycles through the sessions in one state, regardless of `focus_priorities`.
+    FocusNextInState {
+        state: AgentState,
+    },
+    /// Focuses the `n`th session in panel order, counting from 1.
+    FocusIndex {
+        n: usize,
+    },
+    /// Focuses the most urgent session of a group, by group key.
+    FocusGroup {
+        group: String,
+    },
+    /// Focuses the previously focused agent, like alt-tab.
+    FocusLast,
     IdleStatus {
         idle: bool,
     },
@@ -45,31 +186,388 @@ pub enum IncomingMessage {
         enabled: bool,
         focus_delay_ms: u64,
     },
+    /// Turns do-not-disturb on or off by hand, optionally only for
+    /// `duration_secs`.
+    Dnd {
+        enabled: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        duration_secs: Option<u64>,
+    },
     ClearAgents,
     MarkAllStarted,
     WindowClosed {
         session: String,
     },
+    ListSessions {
+        #[serde(default)]
+        state: Option<AgentState>,
+    },
+    GetSession {
+        session: String,
+    },
+    SessionHistory {
+        session: String,
+    },
+    Subscribe {
+        topics: Vec<Topic>,
+    },
+    Unsubscribe {
+        topics: Vec<Topic>,
+    },
+    Resync,
+    Batch {
+        messages: Vec<IncomingMessage>,
+    },
+}
+
+impl IncomingMessage {
+    pub const TYPES: &'static [&'static str] = &[
+        "hello",
+        "state",
+        "window_focus",
+        "session_workspace",
+        "location",
+        "click",
+        "focus_next",
+        "focus_prev",
+        "focus_next_in_state",
+        "focus_index",
+        "focus_group",
+        "focus_last",
+        "idle_status",
+        "auto_focus_config",
+        "dnd",
+        "clear_agents",
+        "mark_all_started",
+        "window_closed",
+        "list_sessions",
+        "get_session",
+        "session_history",
+        "subscribe",
+        "unsubscribe",
+        "resync",
+        "batch",
+    ];
+
+    pub fn parse(line: &str) -> Result<Self, ProtocolError> {
+        serde_json::from_str::<Self>(line).map_err(|e| classify_error(line, e))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum RequestId {
+    Number(u64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct Envelope {
+    #[serde(default)]
+    pub id: Option<RequestId>,
+    #[serde(flatten)]
+    pub message: IncomingMessage,
+}
+
+impl Envelope {
+    pub fn parse(line: &str) -> Result<Self, ProtocolError> {
+        serde_json::from_str::<Self>(line).map_err(|e| classify_error(line, e))
+    }
+}
+
+fn classify_error(line: &str, err: serde_json::Error) -> ProtocolError {
+    let value: serde_json::Value = match serde_json::from_str(line) {
+        Ok(v) => v,
+        Err(e) => return ProtocolError::new(ErrorCode::InvalidJson, None, e.to_string()),
+    };
+
+    let id = value
+        .get("id")
+        .and_then(|id| serde_json::from_value::<RequestId>(id.clone()).ok());
+
+    let mut error = match value.get("type").and_then(|t| t.as_str()) {
+        None => ProtocolError::new(
+            ErrorCode::InvalidMessage,
+            None,
+            "missing string field 'type'".to_string(),
+        ),
+        Some(t) if !IncomingMessage::TYPES.contains(&t) => ProtocolError::new(
+            ErrorCode::Unsupported,
+            Some(t.to_string()),
+            format!("unsupported message type '{}'", t),
+        ),
+        Some(t) => ProtocolError::new(ErrorCode::InvalidMessage, Some(t.to_string()), err.to_string()),
+    };
+    error.id = id;
+    error
 }
 
 fn default_agent_type() -> String {
     "claude".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Optional facts about a session reported by its hook. Fields left out of
+/// a `state` message keep their previously reported value.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SessionMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_branch: Option<String>,
+    /// Top-level directory of the git repository containing `cwd`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    /// Field 22 of `/proc/<pid>/stat` (clock ticks since boot), so a reused
+    /// PID is not mistaken for the agent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid_start_time: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terminal: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_session_id: Option<String>,
+}
+
+impl SessionMetadata {
+    pub fn merge(&mut self, update: SessionMetadata) {
+        let SessionMetadata { cwd, git_branch, repository, pid, pid_start_time, terminal, model, agent_session_id } =
+            update;
+        if cwd.is_some() {
+            self.cwd = cwd;
+        }
+        if git_branch.is_some() {
+            self.git_branch = git_branch;
+        }
+        if repository.is_some() {
+            self.repository = repository;
+        }
+        if pid.is_some() {
+            if pid != self.pid {
+                self.pid_start_time = None;
+            }
+            self.pid = pid;
+        }
+        if pid_start_time.is_some() {
+            self.pid_start_time = pid_start_time;
+        }
+        if terminal.is_some() {
+            self.terminal = terminal;
+        }
+        if model.is_some() {
+            self.model = model;
+        }
+        if agent_session_id.is_some() {
+            self.agent_session_id = agent_session_id;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct AgentInfo {
     pub session: String,
     pub state: AgentState,
     pub focused: bool,
+    /// Position of the session's group in this render, counting from 0.
     pub group: u32,
+    /// Stable id of the group, from the hook's `group`, a config rule or
+    /// `group_by`.
+    pub group_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_label: Option<String>,
     pub agent_type: Arc<str>,
+    /// Unix milliseconds at which the session entered `state`.
+    pub state_since: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_session: Option<String>,
+    /// Most urgent state among this session and its subagents; only set on
+    /// sessions that have subagents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aggregate_state: Option<AgentState>,
+    #[serde(flatten)]
+    pub meta: SessionMetadata,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidJson,
+    InvalidMessage,
+    Unsupported,
+    LineTooLong,
+    /// A well-formed update the daemon dropped, e.g. out of order.
+    Rejected,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolError {
+    pub code: ErrorCode,
+    pub message_type: Option<String>,
+    pub message: String,
+    pub id: Option<RequestId>,
+}
+
+impl ProtocolError {
+    pub fn new(code: ErrorCode, message_type: Option<String>, message: String) -> Self {
+        Self { code, message_type, message, id: None }
+    }
+}
+
+impl From<ProtocolError> for Reply {
+    fn from(e: ProtocolError) -> Self {
+        Reply {
+            id: e.id,
+            message: OutgoingMessage::Error {
+                code: e.code,
+                message_type: e.message_type,
+                message: e.message,
+            },
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// One entry of a session's history: a hook-reported update or a state
+/// change made by the daemon itself (`event` names which).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Transition {
+    /// Unix milliseconds.
+    pub at: u64,
+    pub previous: Option<AgentState>,
+    pub state: AgentState,
+    pub tool: String,
+    pub event: Option<String>,
+}
+
+/// A place to send the user back to, as reported by a `location` message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Location {
+    pub workspace: u32,
+    pub monitor: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SessionSnapshot {
+    #[serde(flatten)]
+    pub agent: AgentInfo,
+    pub tool: String,
+    pub last_activity_at: u64,
+    pub ended_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum OutgoingMessage {
-    Render { agents: Vec<AgentInfo> },
+    Welcome {
+        protocol_version: u32,
+        min_protocol_version: u32,
+        daemon_version: String,
+        features: Vec<String>,
+    },
+    Refused {
+        reason: String,
+        protocol_version: u32,
+        min_protocol_version: u32,
+    },
+    Render { seq: u64, agents: Vec<AgentInfo> },
+    RenderDelta {
+        seq: u64,
+        base_seq: u64,
+        added: Vec<AgentInfo>,
+        changed: Vec<AgentInfo>,
+        removed: Vec<String>,
+        order: Option<Vec<String>>,
+    },
     Focus { session: String, agent_type: String },
     AutoFocus { session: String, agent_type: String },
-    ReturnWorkspace,
+    ReturnWorkspace { destination: Option<Location> },
+    Sessions { sessions: Vec<SessionSnapshot> },
+    Session { session: String, snapshot: Option<Box<SessionSnapshot>> },
+    History { session: String, transitions: Option<Vec<Transition>> },
+    Error {
+        code: ErrorCode,
+        message_type: Option<String>,
+        message: String,
+    },
+    Ack,
+    Subscribed { topics: Vec<Topic> },
+    Lifecycle {
+        session: String,
+        event: LifecycleEvent,
+        previous: Option<AgentState>,
+        state: Option<AgentState>,
+    },
+    /// Do-not-disturb began: auto-focus and `lifecycle` events are held
+    /// back until it ends. `until` is set for a manual one with a duration.
+    DndStarted { reason: DndReason, until: Option<u64> },
+    /// Do-not-disturb ended: what changed since `since`, and the panel as
+    /// it is now.
+    DndEnded { since: u64, changes: Vec<DndChange>, agents: Vec<AgentInfo> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Reply {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<RequestId>,
+    #[serde(flatten)]
+    pub message: OutgoingMessage,
+}
+
+impl OutgoingMessage {
+    pub fn topic(&self) -> Option<Topic> {
+        match self {
+            OutgoingMessage::Render { .. } => Some(Topic::Render),
+            OutgoingMessage::RenderDelta { .. } => Some(Topic::RenderDelta),
+            OutgoingMessage::Focus { .. } => Some(Topic::Focus),
+            OutgoingMessage::AutoFocus { .. } => Some(Topic::AutoFocus),
+            OutgoingMessage::ReturnWorkspace { .. } => Some(Topic::ReturnWorkspace),
+            OutgoingMessage::Lifecycle { .. } => Some(Topic::Lifecycle),
+            OutgoingMessage::DndStarted { .. } | OutgoingMessage::DndEnded { .. } => Some(Topic::Dnd),
+            _ => None,
+        }
+    }
+}
+
+/// JSON Schema for the wire protocol. Every line a client sends is an
+/// `Envelope`, every line the daemon sends is a `Reply`.
+pub fn schema() -> RootSchema {
+    let mut gen = SchemaSettings::draft07().into_generator();
+    let envelope = gen.subschema_for::<Envelope>();
+    let reply = gen.subschema_for::<Reply>();
+    gen.subschema_for::<IncomingMessage>();
+    gen.subschema_for::<OutgoingMessage>();
+    gen.subschema_for::<AgentInfo>();
+    gen.subschema_for::<AgentState>();
+
+    let schema = SchemaObject {
+        metadata: Some(Box::new(Metadata {
+            title: Some("argus-agenticus protocol".to_string()),
+            description: Some(format!(
+                "JSON lines over a Unix socket, protocol version {} (minimum {})",
+                PROTOCOL_VERSION, MIN_PROTOCOL_VERSION
+            )),
+            ..Default::default()
+        })),
+        subschemas: Some(Box::new(SubschemaValidation {
+            one_of: Some(vec![envelope, reply]),
+            ..Default::default()
+        })),
+        extensions: [
+            ("x-protocol-version".to_string(), PROTOCOL_VERSION.into()),
+            ("x-min-protocol-version".to_string(), MIN_PROTOCOL_VERSION.into()),
+            ("x-features".to_string(), FEATURES.into()),
+        ]
+        .into_iter()
+        .collect(),
+        ..Default::default()
+    };
+
+    RootSchema {
+        meta_schema: gen.settings().meta_schema.clone(),
+        definitions: gen.take_definitions(),
+        schema,
+    }
 }
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use tokio::sync::broadcast;
+
+use crate::protocol::{AgentInfo, LifecycleEvent, OutgoingMessage};
+use crate::state::{DndEvent, StateManager};
+
+pub fn changes(state: &mut StateManager, tx: &broadcast::Sender<OutgoingMessage>) {
+    for event in state.take_dnd_events() {
+        let _ = tx.send(match event {
+            DndEvent::Started { reason, until } => OutgoingMessage::DndStarted { reason, until },
+            DndEvent::Ended { since, changes } => {
+                OutgoingMessage::DndEnded { since, changes, agents: state.get_render_data() }
+            }
+        });
+    }
+    let changes = state.take_changes();
+    if !state.has_lifecycle_subscribers() {
+        return;
+    }
+    for change in changes {
+        let event = match (change.previous, change.state) {
+            (None, _) => LifecycleEvent::Added,
+            (_, None) => LifecycleEvent::Removed,
+            _ => LifecycleEvent::Changed,
+        };
+        let _ = tx.send(OutgoingMessage::Lifecycle {
+            session: change.session,
+            event,
+            previous: change.previous,
+            state: change.state,
+        });
+    }
+}
+
+pub fn render(state: &mut StateManager, tx: &broadcast::Sender<OutgoingMessage>) {
+    let seq = state.next_render_seq();
+    let _ = tx.send(OutgoingMessage::Render { seq, agents: state.get_render_data() });
+}
+
+/// Ends an auto-focus run. Under do-not-disturb the user stays put.
+pub fn return_workspace(state: &mut StateManager, tx: &broadcast::Sender<OutgoingMessage>) {
+    let destination = state.take_return_destination();
+    if !state.dnd_active() {
+        let _ = tx.send(OutgoingMessage::ReturnWorkspace { destination });
+    }
+}
+
+pub fn snapshot(state: &StateManager) -> OutgoingMessage {
+    OutgoingMessage::Render { seq: state.render_seq(), agents: state.get_render_data() }
+}
+
+pub fn delta(seq: u64, base_seq: u64, base: &[AgentInfo], agents: &[AgentInfo]) -> Option<OutgoingMessage> {
+    let previous: HashMap<&str, &AgentInfo> =
+        base.iter().map(|a| (a.session.as_str(), a)).collect();
+    let current: HashMap<&str, &AgentInfo> =
+        agents.iter().map(|a| (a.session.as_str(), a)).collect();
+
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    for agent in agents {
+        match previous.get(agent.session.as_str()) {
+            None => added.push(agent.clone()),
+            Some(prev) if *prev != agent => changed.push(agent.clone()),
+            Some(_) => {}
+        }
+    }
+
+    let removed: Vec<String> = base
+        .iter()
+        .filter(|a| !current.contains_key(a.session.as_str()))
+        .map(|a| a.session.clone())
+        .collect();
+
+    let reordered = base.len() != agents.len()
+        || base.iter().zip(agents).any(|(a, b)| a.session != b.session);
+
+    if added.is_empty() && changed.is_empty() && removed.is_empty() && !reordered {
+        return None;
+    }
+
+    Some(OutgoingMessage::RenderDelta {
+        seq,
+        base_seq,
+        added,
+        changed,
+        removed,
+        order: reordered.then(|| agents.iter().map(|a| a.session.clone()).collect()),
+    })
+}
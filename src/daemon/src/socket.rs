@@ -6,31 +6,51 @@ use tokio::signal::{self, unix::{signal, SignalKind}};
 use tokio::sync::{broadcast, Mutex, Notify};
 use tracing::{debug, error, info};
 
+use crate::config::Config;
 use crate::connection::Connection;
+use crate::persist;
 use crate::protocol::OutgoingMessage;
 use crate::state::StateManager;
 use crate::tasks;
 
-const CHANNEL_CAPACITY: usize = 64;
-
 pub struct SocketServer {
     socket_path: PathBuf,
     state: Arc<Mutex<StateManager>>,
     broadcast_tx: broadcast::Sender<OutgoingMessage>,
     auto_focus_notify: Arc<Notify>,
+    state_file: Option<PathBuf>,
+    config_file: Option<PathBuf>,
 }
 
 impl SocketServer {
     pub fn new(socket_path: PathBuf) -> Self {
-        let (broadcast_tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self::with_config(socket_path, Config::default())
+    }
+
+    pub fn with_config(socket_path: PathBuf, config: Config) -> Self {
+        let (broadcast_tx, _) = broadcast::channel(config.channel_capacity);
         Self {
             socket_path,
-            state: Arc::new(Mutex::new(StateManager::new())),
+            state: Arc::new(Mutex::new(StateManager::with_config(config))),
             broadcast_tx,
             auto_focus_notify: Arc::new(Notify::new()),
+            state_file: None,
+            config_file: None,
         }
     }
 
+    /// Re-reads the config from `path` on SIGHUP and whenever it changes.
+    pub fn watch_config(mut self, path: PathBuf) -> Self {
+        self.config_file = Some(path);
+        self
+    }
+
+    /// Restores state from `path` on startup and keeps it saved there.
+    pub fn with_state_file(mut self, path: PathBuf) -> Self {
+        self.state_file = Some(path);
+        self
+    }
+
     pub async fn run(&self) -> std::io::Result<()> {
         if let Some(parent) = self.socket_path.parent() {
             tokio::fs::create_dir_all(parent).await?;
@@ -38,15 +58,38 @@ impl SocketServer {
 
         let _ = tokio::fs::remove_file(&self.socket_path).await;
 
+        if let Some(path) = &self.state_file {
+            if let Some(snapshot) = persist::load(path).await {
+                let restored = self.state.lock().await.restore(snapshot);
+                info!("Restored {} sessions from {:?}", restored, path);
+            }
+        }
+
         let listener = UnixListener::bind(&self.socket_path)?;
         info!("Listening on {:?}", self.socket_path);
 
         tasks::spawn_cleanup(Arc::clone(&self.state), self.broadcast_tx.clone());
+        tasks::spawn_liveness(
+            Arc::clone(&self.state),
+            self.broadcast_tx.clone(),
+            Arc::clone(&self.auto_focus_notify),
+        );
+        tasks::spawn_dnd(
+            Arc::clone(&self.state),
+            self.broadcast_tx.clone(),
+            Arc::clone(&self.auto_focus_notify),
+        );
         tasks::spawn_auto_focus(
             Arc::clone(&self.state),
             self.broadcast_tx.clone(),
             Arc::clone(&self.auto_focus_notify),
         );
+        if let Some(path) = &self.state_file {
+            persist::spawn_saver(Arc::clone(&self.state), path.clone());
+        }
+        if let Some(path) = &self.config_file {
+            tasks::spawn_config_watcher(Arc::clone(&self.state), self.broadcast_tx.clone(), path.clone())?;
+        }
 
         let mut sigterm = signal(SignalKind::terminate())?;
 
@@ -73,6 +116,12 @@ impl SocketServer {
         }
 
         info!("Shutting down...");
+        if let Some(path) = &self.state_file {
+            let snapshot = self.state.lock().await.to_snapshot();
+            if let Err(e) = persist::save(path, &snapshot).await {
+                error!("Cannot write state snapshot {:?}: {}", path, e);
+            }
+        }
         let _ = tokio::fs::remove_file(&self.socket_path).await;
         Ok(())
     }
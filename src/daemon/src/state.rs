@@ -1,44 +1,194 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::protocol::{AgentInfo, AgentState};
+use crate::config::{AutoFocusPolicy, ClockTime, Config, GroupBy, Weekday};
+use crate::persist::{PersistedSession, Snapshot, SNAPSHOT_VERSION};
+use crate::protocol::{AgentInfo, AgentState, DndChange, DndReason, Location, SessionMetadata, SessionSnapshot, Transition};
 
-const ENDED_HIDE_DELAY: Duration = Duration::from_secs(10);
-const STALE_TIMEOUT: Duration = Duration::from_secs(30);
-const FOCUS_PRIORITIES: &[AgentState] = &[
-    AgentState::Awaiting,
-    AgentState::Completed,
-    AgentState::Started,
-];
+const HISTORY_LIMIT: usize = 32;
+const FOCUS_HISTORY_LIMIT: usize = 16;
+/// Window the `auto_focus_max_per_minute` cap counts switches over.
+const MINUTE: Duration = Duration::from_secs(60);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum AutoFocusEvent {
     Trigger,
     QueueEmpty,
+    #[default]
     None,
 }
 
+/// A do-not-disturb transition for clients to hear about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DndEvent {
+    /// Turned on, or its reason or expiry changed while on.
+    Started { reason: DndReason, until: Option<u64> },
+    Ended { since: u64, changes: Vec<DndChange> },
+}
+
+/// The do-not-disturb period in effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Dnd {
+    since: u64,
+    reason: DndReason,
+    until: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionChange {
+    pub session: String,
+    pub previous: Option<AgentState>,
+    pub state: Option<AgentState>,
+}
+
 #[derive(Debug, Clone)]
 pub struct SessionInfo {
     pub state: AgentState,
-    #[allow(dead_code)]
     pub tool: String,
     pub agent_type: Arc<str>,
     pub ended_at: Option<Instant>,
     pub last_activity: Instant,
+    /// Unix milliseconds at which the session entered `state`.
+    pub state_since: u64,
+    pub meta: SessionMetadata,
+    /// Recent updates, oldest first, bounded by `HISTORY_LIMIT`.
+    pub history: VecDeque<Transition>,
+    /// Highest hook `seq` applied so far.
+    pub last_seq: Option<u64>,
+    /// Session that spawned this one, if it is a subagent.
+    pub parent: Option<String>,
+    /// Group and label reported by the hook; they win over config rules.
+    pub explicit_group: Option<String>,
+    pub explicit_label: Option<String>,
+    /// Effective group, recomputed whenever its inputs change.
+    pub group: String,
+    pub group_label: Option<String>,
+}
+
+/// Why a hook update was not applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// Sent before the last applied update but delivered after it.
+    Stale { seq: u64, last_seq: u64 },
+    /// Not allowed by `AgentState::accepts`.
+    Illegal { from: AgentState, to: AgentState },
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Stale { seq, last_seq } => {
+                write!(f, "Stale update: seq {} is older than the last applied {}", seq, last_seq)
+            }
+            Rejection::Illegal { from, to } => write!(f, "Illegal transition from {:?} to {:?}", from, to),
+        }
+    }
+}
+
+impl SessionInfo {
+    fn new(session: &str, state: AgentState, tool: String, agent_type: Arc<str>) -> Self {
+        Self {
+            state,
+            tool,
+            agent_type,
+            ended_at: None,
+            last_activity: Instant::now(),
+            state_since: now_millis(),
+            meta: SessionMetadata::default(),
+            history: VecDeque::new(),
+            last_seq: None,
+            parent: None,
+            explicit_group: None,
+            explicit_label: None,
+            group: window_key(session).to_string(),
+            group_label: None,
+        }
+    }
+
+    /// Recomputes `group`: the hook's explicit group, else the first matching
+    /// `group_rules` entry, else `group_by`.
+    fn regroup(&mut self, session: &str, config: &Config) {
+        let (group, label) = if let Some(group) = &self.explicit_group {
+            (group.clone(), None)
+        } else if let Some(rule) = config.group_rules.iter().find(|r| r.matches(session, &self.agent_type, &self.meta)) {
+            (rule.group.clone(), rule.label.clone())
+        } else {
+            let by_path = |path: &Option<String>| {
+                path.as_ref().map(|p| (p.clone(), Some(basename(p).to_string())))
+            };
+            match config.group_by {
+                GroupBy::SessionId => None,
+                GroupBy::Repository => by_path(&self.meta.repository),
+                GroupBy::Cwd => by_path(&self.meta.cwd),
+            }
+            .unwrap_or_else(|| (window_key(session).to_string(), None))
+        };
+        self.group = group;
+        self.group_label = self.explicit_label.clone().or(label);
+    }
+
+    fn enter(&mut self, state: AgentState, event: &str) {
+        if self.state != state {
+            let previous = self.state;
+            self.state = state;
+            self.state_since = now_millis();
+            self.push_history(Some(previous), event.to_string());
+        }
+    }
+
+    fn push_history(&mut self, previous: Option<AgentState>, event: impl Into<Option<String>>) {
+        if self.history.len() == HISTORY_LIMIT {
+            self.history.pop_front();
+        }
+        self.history.push_back(Transition {
+            at: now_millis(),
+            previous,
+            state: self.state,
+            tool: self.tool.clone(),
+            event: event.into(),
+        });
+    }
 }
 
 pub struct StateManager {
     sessions: HashMap<String, SessionInfo>,
     workspaces: HashMap<String, (u32, u32)>,
     focused_group: Option<String>,
-    last_focus_index: usize,
+    /// Sort key of the session `focus_next` last picked, per priority tier.
+    focus_cursors: HashMap<AgentState, FocusKey>,
+    /// Sessions whose window was focused, most recent first.
+    focus_history: VecDeque<String>,
     awaiting_queue: Vec<String>,
     auto_focus_enabled: bool,
     focus_delay_ms: u64,
     user_idle: bool,
     auto_focus_active: bool,
+    /// When auto-focus switched, newest last; kept for the last minute.
+    auto_focus_switches: VecDeque<Instant>,
+    /// When focus last left an agent window for another window.
+    returned_at: Option<Instant>,
+    /// Last location a client reported, and where the current auto-focus
+    /// run sends the user back to.
+    location: Option<Location>,
+    return_to: Option<Location>,
+    /// Do-not-disturb turned on by hand: `Some(None)` until turned off,
+    /// `Some(Some(ms))` until that Unix time.
+    dnd_manual: Option<Option<u64>>,
+    /// Turned off by hand during quiet hours; cleared once they are over.
+    quiet_overridden: bool,
+    dnd: Option<Dnd>,
+    /// Lifecycle changes held back while do-not-disturb is on.
+    dnd_held: Vec<SessionChange>,
+    dnd_events: Vec<DndEvent>,
+    changes: Vec<SessionChange>,
+    /// Connections subscribed to `lifecycle`; with none, changes are not
+    /// broadcast at all.
+    lifecycle_subscribers: usize,
+    render_seq: u64,
+    config: Config,
 }
 
 impl StateManager {
@@ -47,42 +197,98 @@ impl StateManager {
             sessions: HashMap::new(),
             workspaces: HashMap::new(),
             focused_group: None,
-            last_focus_index: 0,
+            focus_cursors: HashMap::new(),
+            focus_history: VecDeque::new(),
             awaiting_queue: Vec::new(),
             auto_focus_enabled: false,
             focus_delay_ms: 1000,
             user_idle: false,
             auto_focus_active: false,
+            auto_focus_switches: VecDeque::new(),
+            returned_at: None,
+            location: None,
+            return_to: None,
+            dnd_manual: None,
+            quiet_overridden: false,
+            dnd: None,
+            dnd_held: Vec::new(),
+            dnd_events: Vec::new(),
+            changes: Vec::new(),
+            lifecycle_subscribers: 0,
+            render_seq: 0,
+            config: Config::default(),
         }
     }
 
+    pub fn with_config(config: Config) -> Self {
+        Self { config, ..Self::new() }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Swaps in a new config and regroups every session. Returns whether any
+    /// group or label changed, in which case the render is out of date.
+    pub fn set_config(&mut self, config: Config) -> bool {
+        self.config = config;
+        let mut regrouped = false;
+        for (session, info) in self.sessions.iter_mut() {
+            let before = (info.group.clone(), info.group_label.clone());
+            info.regroup(session, &self.config);
+            regrouped |= before != (info.group.clone(), info.group_label.clone());
+        }
+        // The focused window still holds the session it did; follow it into
+        // its new group.
+        if regrouped && self.focused_group.is_some() {
+            if let Some(info) = self.focus_history.front().and_then(|s| self.sessions.get(s)) {
+                self.focused_group = Some(info.group.clone());
+            }
+        }
+        regrouped
+    }
+
     pub fn update_state(&mut self, session: String, state: AgentState, tool: String, agent_type: Arc<str>) -> AutoFocusEvent {
-        let prev_state = self.sessions.get(&session).map(|s| s.state);
+        self.update_state_event(session, state, tool, agent_type, None)
+    }
 
-        let ended_at = if state == AgentState::Ended {
+    /// Like `update_state`, additionally recording the hook event that
+    /// reported it in the session's history.
+    pub fn update_state_event(
+        &mut self,
+        session: String,
+        state: AgentState,
+        tool: String,
+        agent_type: Arc<str>,
+        event: Option<String>,
+    ) -> AutoFocusEvent {
+        let prev = self.sessions.remove(&session);
+        let prev_state = prev.as_ref().map(|s| s.state);
+        let mut info = prev.unwrap_or_else(|| SessionInfo::new(&session, state, String::new(), Arc::clone(&agent_type)));
+        info.tool = tool;
+        info.agent_type = agent_type;
+        info.ended_at = if state == AgentState::Ended {
             Some(Instant::now())
         } else {
             None
         };
+        info.last_activity = Instant::now();
+        info.regroup(&session, &self.config);
 
         let mut actual_state = state;
-        if state == AgentState::Completed {
-            let group = Self::get_group(&session);
-            if self.focused_group.as_deref() == Some(group) {
-                actual_state = AgentState::Started;
-            }
+        if state == AgentState::Completed && self.focused_group.as_deref() == Some(info.group.as_str()) {
+            actual_state = AgentState::Started;
         }
 
-        self.sessions.insert(
-            session.clone(),
-            SessionInfo {
-                state: actual_state,
-                tool,
-                agent_type,
-                ended_at,
-                last_activity: Instant::now(),
-            },
-        );
+        if prev_state != Some(actual_state) {
+            info.state_since = now_millis();
+        }
+        info.state = actual_state;
+        info.push_history(prev_state, event);
+        self.sessions.insert(session.clone(), info);
+        if prev_state != Some(actual_state) {
+            self.record(&session, prev_state, Some(actual_state));
+        }
 
         let became_awaiting = actual_state == AgentState::Awaiting
             && prev_state != Some(AgentState::Awaiting);
@@ -98,8 +304,7 @@ impl StateManager {
 
         if left_awaiting || actual_state == AgentState::Ended {
             self.awaiting_queue.retain(|s| s != &session);
-            if self.awaiting_queue.is_empty() && self.auto_focus_active {
-                self.auto_focus_active = false;
+            if self.finish_auto_focus_run() == AutoFocusEvent::QueueEmpty {
                 return AutoFocusEvent::QueueEmpty;
             }
             if left_awaiting {
@@ -110,29 +315,130 @@ impl StateManager {
         AutoFocusEvent::None
     }
 
+    /// Applies a state reported by a hook, unless it arrived out of order
+    /// (`seq` below the last applied one) or the transition is illegal.
+    pub fn apply_hook_update(
+        &mut self,
+        session: String,
+        state: AgentState,
+        tool: String,
+        agent_type: Arc<str>,
+        event: Option<String>,
+        seq: Option<u64>,
+    ) -> Result<AutoFocusEvent, Rejection> {
+        if let Some(info) = self.sessions.get(&session) {
+            if let (Some(seq), Some(last_seq)) = (seq, info.last_seq) {
+                if seq < last_seq {
+                    return Err(Rejection::Stale { seq, last_seq });
+                }
+            }
+            if !info.state.accepts(state) {
+                return Err(Rejection::Illegal { from: info.state, to: state });
+            }
+        }
+
+        let result = self.update_state_event(session.clone(), state, tool, agent_type, event);
+        if let (Some(seq), Some(info)) = (seq, self.sessions.get_mut(&session)) {
+            info.last_seq = Some(seq);
+        }
+        Ok(result)
+    }
+
+    pub fn update_metadata(&mut self, session: &str, meta: SessionMetadata) {
+        if let Some(info) = self.sessions.get_mut(session) {
+            info.meta.merge(meta);
+            info.regroup(session, &self.config);
+        }
+    }
+
+    /// Sets the hook-reported group and label. Either left out keeps its
+    /// previous value.
+    pub fn set_group(&mut self, session: &str, group: Option<String>, label: Option<String>) {
+        if let Some(info) = self.sessions.get_mut(session) {
+            if group.is_some() {
+                info.explicit_group = group;
+            }
+            if label.is_some() {
+                info.explicit_label = label;
+            }
+            info.regroup(session, &self.config);
+        }
+    }
+
+    /// Records `parent` as the session that spawned `session`. Ignored if it
+    /// would make a session its own ancestor.
+    pub fn set_parent(&mut self, session: &str, parent: String) {
+        if self.ancestors(&parent).any(|a| a == session) {
+            return;
+        }
+        if let Some(info) = self.sessions.get_mut(session) {
+            info.parent = Some(parent);
+        }
+    }
+
+    /// `session` followed by its known parents, nearest first.
+    fn ancestors<'a>(&'a self, session: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        std::iter::successors(Some(session), move |s| {
+            self.sessions.get(*s).and_then(|info| info.parent.as_deref())
+        })
+        .take(self.sessions.len() + 1)
+    }
+
+    /// Subagents have no window of their own, so focusing one focuses the
+    /// outermost known parent.
+    pub fn focus_target(&self, session: &str) -> String {
+        self.ancestors(session)
+            .filter(|s| self.sessions.contains_key(*s))
+            .last()
+            .unwrap_or(session)
+            .to_string()
+    }
+
     pub fn update_window_focus(&mut self, title: &str, agent_type: Option<&str>) -> bool {
-        let new_focused = self
+        // Terminal titles carry the session id prefix, not the group.
+        let focused_session = self
             .sessions
-            .keys()
-            .find(|s| title.contains(Self::get_group(s)))
-            .map(|s| Self::get_group(s).to_string())
+            .iter()
+            .find(|(s, _)| title.contains(window_key(s)))
             .or_else(|| {
                 let at = agent_type.filter(|a| !a.is_empty())?;
-                self.sessions
-                    .iter()
-                    .find(|(_, info)| &*info.agent_type == at)
-                    .map(|(s, _)| Self::get_group(s).to_string())
-            });
+                self.sessions.iter().find(|(_, info)| &*info.agent_type == at)
+            })
+            .map(|(s, info)| (s.clone(), info.group.clone()));
+        let new_focused = focused_session.as_ref().map(|(_, group)| group.clone());
+        if let Some((session, _)) = focused_session {
+            self.focus_history.retain(|s| *s != session);
+            self.focus_history.push_front(session);
+            self.focus_history.truncate(FOCUS_HISTORY_LIMIT);
+        }
 
         let changed = self.focused_group != new_focused;
+        if self.focused_group.is_some() && new_focused.is_none() {
+            self.returned_at = Some(Instant::now());
+        }
+        // Moving on by hand, to another window or to an agent that is not
+        // waiting, cancels the return.
+        let to_awaiting = new_focused.as_ref().is_some_and(|group| {
+            self.sessions
+                .values()
+                .any(|info| info.group == *group && info.state == AgentState::Awaiting)
+        });
+        if !to_awaiting {
+            self.return_to = None;
+        }
         self.focused_group = new_focused;
 
         if let Some(ref group) = self.focused_group {
             for (session, info) in self.sessions.iter_mut() {
-                if Self::get_group(session) == group
+                if info.group == *group
                     && info.state == AgentState::Completed
                 {
-                    info.state = AgentState::Started;
+                    info.enter(AgentState::Started, "window_focus");
+                    self.changes.push(SessionChange {
+                        session: session.clone(),
+                        previous: Some(AgentState::Completed),
+                        state: Some(AgentState::Started),
+                    });
                 }
             }
         }
@@ -143,7 +449,13 @@ impl StateManager {
     pub fn remove_session(&mut self, session: &str) -> bool {
         self.awaiting_queue.retain(|s| s != session);
         self.workspaces.remove(session);
-        self.sessions.remove(session).is_some()
+        match self.sessions.remove(session) {
+            Some(info) => {
+                self.record(session, Some(info.state), None);
+                true
+            }
+            None => false,
+        }
     }
 
     pub fn get_agent_type(&self, session: &str) -> String {
@@ -156,24 +468,39 @@ impl StateManager {
     pub fn cleanup_ended(&mut self) -> bool {
         let now = Instant::now();
         let mut changed = false;
+        let config = &self.config;
 
         for (session, info) in self.sessions.iter_mut() {
+            let policy = config.policy(&info.agent_type);
             if info.ended_at.is_none()
-                && &*info.agent_type != "claude"
+                && !policy.session_end_reliable
                 && info.state == AgentState::Started
-                && now.duration_since(info.last_activity) >= STALE_TIMEOUT
-                && self.focused_group.as_deref() != Some(Self::get_group(session))
+                && now.duration_since(info.last_activity) >= policy.stale_timeout
+                && self.focused_group.as_deref() != Some(info.group.as_str())
             {
-                info.state = AgentState::Ended;
+                info.enter(AgentState::Ended, "stale");
                 info.ended_at = Some(now);
+                self.changes.push(SessionChange {
+                    session: session.clone(),
+                    previous: Some(AgentState::Started),
+                    state: Some(AgentState::Ended),
+                });
                 changed = true;
             }
         }
 
         let before = self.sessions.len();
-        self.sessions.retain(|_, info| {
+        let changes = &mut self.changes;
+        self.sessions.retain(|session, info| {
             if let Some(ended_at) = info.ended_at {
-                return now.duration_since(ended_at) < ENDED_HIDE_DELAY;
+                if now.duration_since(ended_at) >= config.policy(&info.agent_type).ended_hide_delay {
+                    changes.push(SessionChange {
+                        session: session.clone(),
+                        previous: Some(info.state),
+                        state: None,
+                    });
+                    return false;
+                }
             }
             true
         });
@@ -181,15 +508,57 @@ impl StateManager {
         changed || self.sessions.len() != before
     }
 
+    /// Ends every live session whose reported process has exited or whose
+    /// PID now belongs to another process. Returns `None` if nothing changed.
+    pub fn reap_dead(&mut self) -> Option<AutoFocusEvent> {
+        let dead: Vec<(String, String, Arc<str>)> = self
+            .sessions
+            .iter()
+            .filter(|(_, info)| info.state != AgentState::Ended && !process_alive(&info.meta))
+            .map(|(session, info)| (session.clone(), info.tool.clone(), Arc::clone(&info.agent_type)))
+            .collect();
+        if dead.is_empty() {
+            return None;
+        }
+
+        let mut result = AutoFocusEvent::None;
+        for (session, tool, agent_type) in dead {
+            let event = self.update_state_event(
+                session,
+                AgentState::Ended,
+                tool,
+                agent_type,
+                Some("process_exit".to_string()),
+            );
+            if event != AutoFocusEvent::None {
+                result = event;
+            }
+        }
+        Some(result)
+    }
+
     pub fn update_workspace(&mut self, session: &str, workspace: u32, monitor: u32) {
-        let group = Self::get_group(session);
-        self.workspaces.insert(group.to_string(), (workspace, monitor));
+        self.workspaces.insert(window_key(session).to_string(), (workspace, monitor));
     }
 
     fn get_placement(&self, session: &str) -> (u32, u32) {
         self.workspaces.get(session).copied().unwrap_or((999, 0))
     }
 
+    /// `(monitor, workspace)` of each group: the first placement among the
+    /// windows of its sessions.
+    fn group_placements(&self) -> HashMap<&str, (u32, u32)> {
+        let mut placements: HashMap<&str, (u32, u32)> = HashMap::new();
+        for (session, info) in &self.sessions {
+            let (ws, mon) = self.get_placement(window_key(session));
+            placements
+                .entry(info.group.as_str())
+                .and_modify(|p| *p = (*p).min((mon, ws)))
+                .or_insert((mon, ws));
+        }
+        placements
+    }
+
     pub fn set_idle(&mut self, idle: bool) {
         self.user_idle = idle;
     }
@@ -200,53 +569,238 @@ impl StateManager {
     }
 
     pub fn should_auto_focus(&self) -> bool {
-        self.auto_focus_enabled && self.user_idle && !self.awaiting_queue.is_empty()
+        self.auto_focus_enabled && self.user_idle && !self.awaiting_queue.is_empty() && self.dnd.is_none()
+    }
+
+    /// Turns manual do-not-disturb on, for `duration` if given, or off.
+    /// Turning it off during quiet hours also ends them early. Takes effect
+    /// on the next `refresh_dnd`.
+    pub fn set_dnd(&mut self, enabled: bool, duration: Option<Duration>) {
+        if enabled {
+            self.dnd_manual = Some(duration.map(|d| {
+                now_millis().saturating_add(u64::try_from(d.as_millis()).unwrap_or(u64::MAX))
+            }));
+            self.quiet_overridden = false;
+        } else {
+            self.dnd_manual = None;
+            self.quiet_overridden = true;
+        }
+    }
+
+    /// Re-evaluates do-not-disturb at the given local time, queueing a
+    /// `DndEvent` if it changed. Returns whether it just ended.
+    pub fn refresh_dnd(&mut self, day: Weekday, time: ClockTime) -> bool {
+        let now = now_millis();
+        if self.dnd_manual.is_some_and(|until| until.is_some_and(|t| t <= now)) {
+            self.dnd_manual = None;
+        }
+        let quiet = self.config.in_quiet_hours(day, time);
+        if !quiet {
+            self.quiet_overridden = false;
+        }
+        let reason = match self.dnd_manual {
+            Some(_) => Some(DndReason::Manual),
+            None if quiet && !self.quiet_overridden => Some(DndReason::QuietHours),
+            None => None,
+        };
+
+        match (self.dnd, reason) {
+            (None, None) => false,
+            (Some(dnd), None) => {
+                self.dnd = None;
+                self.dnd_held.append(&mut self.changes);
+                let changes = net_changes(std::mem::take(&mut self.dnd_held));
+                self.dnd_events.push(DndEvent::Ended { since: dnd.since, changes });
+                true
+            }
+            (current, Some(reason)) => {
+                let until = self.dnd_manual.flatten();
+                if current.is_none_or(|dnd| (dnd.reason, dnd.until) != (reason, until)) {
+                    let since = current.map_or(now, |dnd| dnd.since);
+                    self.dnd = Some(Dnd { since, reason, until });
+                    self.dnd_events.push(DndEvent::Started { reason, until });
+                }
+                false
+            }
+        }
+    }
+
+    pub fn dnd_active(&self) -> bool {
+        self.dnd.is_some()
+    }
+
+    pub fn take_dnd_events(&mut self) -> Vec<DndEvent> {
+        std::mem::take(&mut self.dnd_events)
     }
 
     pub fn focus_delay_ms(&self) -> u64 {
         self.focus_delay_ms
     }
 
+    /// How long auto-focus must still hold off, or `None` if it may switch
+    /// now: the dwell after its last switch, the cool-down after the user
+    /// returned from an agent window, and the per-minute cap.
+    pub fn auto_focus_hold(&self) -> Option<Duration> {
+        let now = Instant::now();
+        let remaining = |since: Instant, period: Duration| period.saturating_sub(now.duration_since(since));
+        let mut hold = Duration::ZERO;
+        if let Some(&last) = self.auto_focus_switches.back() {
+            hold = hold.max(remaining(last, self.config.auto_focus_dwell()));
+        }
+        if let Some(returned) = self.returned_at {
+            hold = hold.max(remaining(returned, self.config.auto_focus_return_cooldown()));
+        }
+        let cap = self.config.auto_focus_max_per_minute as usize;
+        if cap > 0 {
+            let switches = &self.auto_focus_switches;
+            if switches.len() >= cap {
+                hold = hold.max(remaining(switches[switches.len() - cap], MINUTE));
+            }
+        }
+        (!hold.is_zero()).then_some(hold)
+    }
+
+    pub fn set_location(&mut self, location: Location) {
+        self.location = Some(location);
+    }
+
+    /// Where the auto-focus run that just ended should send the user back
+    /// to. `None` when no client reported a location before the run, or the
+    /// user already moved on.
+    pub fn take_return_destination(&mut self) -> Option<Location> {
+        self.return_to.take()
+    }
+
+    /// Ends the auto-focus run once its queue has drained, however that
+    /// happened, so the next run records a fresh return destination.
+    pub fn finish_auto_focus_run(&mut self) -> AutoFocusEvent {
+        if self.awaiting_queue.is_empty() && self.auto_focus_active {
+            self.auto_focus_active = false;
+            return AutoFocusEvent::QueueEmpty;
+        }
+        AutoFocusEvent::None
+    }
+
     pub fn next_awaiting(&mut self) -> Option<String> {
         if self.awaiting_queue.is_empty() {
             return None;
         }
+        if !self.auto_focus_active {
+            self.return_to = self.location.clone();
+        }
         self.auto_focus_active = true;
-        Some(self.awaiting_queue[0].clone())
+        let switched = Instant::now();
+        self.auto_focus_switches.push_back(switched);
+        self.auto_focus_switches.retain(|t| switched.duration_since(*t) < MINUTE);
+        let here = self.current_location();
+        let now = now_millis();
+        self.awaiting_queue
+            .iter()
+            .enumerate()
+            .min_by_key(|(i, s)| (Reverse(self.focus_priority(s)), self.auto_focus_cost(s, here, now), *i))
+            .map(|(_, s)| self.focus_target(s))
+    }
+
+    /// Rank of an awaiting session under `auto_focus_policy`; lower goes
+    /// first, ties keep queue order.
+    fn auto_focus_cost(&self, session: &str, here: Option<(u32, u32)>, now: u64) -> i64 {
+        let (ws, mon) = self.get_placement(window_key(session));
+        let info = &self.sessions[session];
+        let same_monitor = here.is_some_and(|(m, _)| m == mon);
+        match self.config.auto_focus_policy {
+            AutoFocusPolicy::Fifo => 0,
+            AutoFocusPolicy::LongestWaiting => info.state_since as i64,
+            AutoFocusPolicy::CurrentMonitor => i64::from(!same_monitor),
+            AutoFocusPolicy::NearestWorkspace => match here {
+                Some((_, here_ws)) => {
+                    i64::from(!same_monitor) << 32 | i64::from(ws.abs_diff(here_ws))
+                }
+                None => 0,
+            },
+            AutoFocusPolicy::Weighted => {
+                let w = &self.config.auto_focus_weights;
+                let project = self.config.project_priorities.get(&info.group).copied().unwrap_or(0);
+                let minutes = (now.saturating_sub(info.state_since) / 60_000) as i64;
+                let same_workspace = here == Some((mon, ws));
+                // Weights come from the config file; saturate rather than
+                // panic on extreme values.
+                [
+                    w.project.saturating_mul(i64::from(project)),
+                    w.waiting_minute.saturating_mul(minutes),
+                    w.same_monitor.saturating_mul(i64::from(same_monitor)),
+                    w.same_workspace.saturating_mul(i64::from(same_workspace)),
+                ]
+                .into_iter()
+                .fold(0, i64::saturating_add)
+                .saturating_neg()
+            }
+        }
+    }
+
+    fn focus_priority(&self, session: &str) -> i32 {
+        self.sessions
+            .get(session)
+            .map_or(0, |info| self.config.policy(&info.agent_type).focus_priority)
     }
 
     pub fn clear_all(&mut self) {
-        self.sessions.clear();
+        for (session, info) in self.sessions.drain() {
+            self.changes.push(SessionChange {
+                session,
+                previous: Some(info.state),
+                state: None,
+            });
+        }
         self.workspaces.clear();
         self.awaiting_queue.clear();
         self.focused_group = None;
-        self.last_focus_index = 0;
+        self.focus_cursors.clear();
+        self.focus_history.clear();
     }
 
     pub fn mark_all_started(&mut self) {
-        for info in self.sessions.values_mut() {
+        for (session, info) in self.sessions.iter_mut() {
             if info.state == AgentState::Awaiting {
-                info.state = AgentState::Started;
+                info.enter(AgentState::Started, "mark_all_started");
+                self.changes.push(SessionChange {
+                    session: session.clone(),
+                    previous: Some(AgentState::Awaiting),
+                    state: Some(AgentState::Started),
+                });
             }
         }
         self.awaiting_queue.clear();
     }
 
     pub fn get_render_data(&self) -> Vec<AgentInfo> {
-        let mut keys: Vec<&String> = self.sessions.keys().collect();
-        keys.sort_by(|a, b| {
-            let (ws_a, mon_a) = self.get_placement(Self::get_group(a));
-            let (ws_b, mon_b) = self.get_placement(Self::get_group(b));
-            mon_a.cmp(&mon_b).then(ws_a.cmp(&ws_b)).then_with(|| a.cmp(b))
-        });
+        // Subagents sort and group with their outermost parent, right after it.
+        let placements = self.group_placements();
+        let mut keys: Vec<_> = self
+            .sessions
+            .keys()
+            .map(|s| {
+                let mut chain: Vec<&str> = self.ancestors(s).filter(|a| self.sessions.contains_key(*a)).collect();
+                chain.reverse();
+                let group = self.sessions[chain[0]].group.as_str();
+                (placements[group], group, chain, s)
+            })
+            .collect();
+        keys.sort_unstable();
 
+        // A label reported by any session names the whole group.
+        let mut labels: HashMap<&str, &str> = HashMap::new();
+        for (_, group, chain, _) in &keys {
+            if let Some(label) = &self.sessions[chain[0]].group_label {
+                labels.entry(group).or_insert(label);
+            }
+        }
+        let parents: HashSet<&str> = self.sessions.values().filter_map(|i| i.parent.as_deref()).collect();
         let mut agents = Vec::with_capacity(keys.len());
         let mut group = 0u32;
         let mut prev_group: Option<&str> = None;
 
-        for session in keys {
+        for &(_, g, _, session) in &keys {
             let info = &self.sessions[session];
-            let g = Self::get_group(session);
             if prev_group != Some(g) {
                 if prev_group.is_some() {
                     group += 1;
@@ -259,40 +813,268 @@ impl StateManager {
                 state: info.state,
                 focused,
                 group,
+                group_key: g.to_string(),
+                group_label: labels.get(g).map(|l| l.to_string()),
                 agent_type: info.agent_type.clone(),
+                state_since: info.state_since,
+                parent_session: info.parent.clone(),
+                aggregate_state: if parents.contains(session.as_str()) {
+                    self.aggregate_state(session)
+                } else {
+                    None
+                },
+                meta: info.meta.clone(),
             });
         }
 
         agents
     }
 
+    /// Most urgent state across `session` and every session below it.
+    fn aggregate_state(&self, session: &str) -> Option<AgentState> {
+        self.sessions
+            .iter()
+            .filter(|(s, _)| self.ancestors(s).any(|a| a == session))
+            .map(|(_, info)| info.state)
+            .max_by_key(|s| urgency(*s))
+    }
+
+    pub fn get_history(&self, session: &str) -> Option<Vec<Transition>> {
+        self.sessions.get(session).map(|info| info.history.iter().cloned().collect())
+    }
+
+    pub fn get_session_snapshots(&self) -> Vec<SessionSnapshot> {
+        self.get_render_data()
+            .into_iter()
+            .map(|agent| {
+                let info = &self.sessions[&agent.session];
+                SessionSnapshot {
+                    tool: info.tool.clone(),
+                    last_activity_at: unix_millis(info.last_activity),
+                    ended_at: info.ended_at.map(unix_millis),
+                    agent,
+                }
+            })
+            .collect()
+    }
+
+    /// Cycles through the sessions of the most urgent non-empty tier in
+    /// `focus_priorities`, limited to `focus_scope`. Each tier keeps its own
+    /// cursor, so sessions appearing or leaving don't skip anyone.
     pub fn focus_next(&mut self) -> Option<String> {
-        for priority_state in FOCUS_PRIORITIES {
-            let mut matching: Vec<&String> = self
-                .sessions
+        self.cycle(true)
+    }
+
+    /// Like `focus_next`, walking the tier backwards.
+    pub fn focus_prev(&mut self) -> Option<String> {
+        self.cycle(false)
+    }
+
+    /// Cycles through the sessions in `state` alone, sharing that state's
+    /// cursor with `focus_next`.
+    pub fn focus_next_in_state(&mut self, state: AgentState) -> Option<String> {
+        self.step(state, true)
+    }
+
+    fn cycle(&mut self, forward: bool) -> Option<String> {
+        for i in 0..self.config.focus_priorities.len() {
+            let tier = self.config.focus_priorities[i];
+            if let Some(session) = self.step(tier, forward) {
+                return Some(session);
+            }
+        }
+        None
+    }
+
+    /// Moves the cursor of `tier` one session on, wrapping around.
+    fn step(&mut self, tier: AgentState, forward: bool) -> Option<String> {
+        let here = self.current_location();
+        let scope = self.config.focus_scope;
+        let mut matching: Vec<(FocusKey, &String)> = self
+            .sessions
+            .iter()
+            .filter(|(_, info)| info.state == tier)
+            .map(|(s, _)| {
+                let (ws, mon) = self.get_placement(window_key(s));
+                ((Reverse(self.focus_priority(s)), mon, ws, s.clone()), s)
+            })
+            .filter(|((_, mon, ws, _), _)| scope.contains(here, (*mon, *ws)))
+            .collect();
+        matching.sort();
+
+        let cursor = self.focus_cursors.get(&tier);
+        let next = if forward {
+            cursor
+                .and_then(|cursor| matching.iter().find(|(key, _)| key > cursor))
+                .or(matching.first())
+        } else {
+            cursor
+                .and_then(|cursor| matching.iter().rev().find(|(key, _)| key < cursor))
+                .or(matching.last())
+        }?;
+        let (key, session) = (next.0.clone(), next.1.clone());
+        self.focus_cursors.insert(tier, key);
+        Some(self.focus_target(&session))
+    }
+
+    /// The session behind the `n`th dot of the panel, counting from 1.
+    pub fn focus_index(&self, n: usize) -> Option<String> {
+        let agents = self.get_render_data();
+        let agent = agents.get(n.checked_sub(1)?)?;
+        Some(self.focus_target(&agent.session))
+    }
+
+    /// The most urgent session of `group`, ties broken like `focus_next`.
+    pub fn focus_group(&self, group: &str) -> Option<String> {
+        self.sessions
+            .iter()
+            .filter(|(_, info)| info.group == group)
+            .min_by_key(|(s, info)| {
+                let (ws, mon) = self.get_placement(window_key(s));
+                (Reverse(urgency(info.state)), Reverse(self.focus_priority(s)), mon, ws, s.as_str())
+            })
+            .map(|(s, _)| self.focus_target(s))
+    }
+
+    /// The most recently focused session outside the focused group, for
+    /// switching back and forth between two agents.
+    pub fn focus_last(&self) -> Option<String> {
+        let current = self.focused_group.as_deref();
+        self.focus_history
+            .iter()
+            .find(|s| self.sessions.get(*s).is_some_and(|info| Some(info.group.as_str()) != current))
+            .map(|s| self.focus_target(s))
+    }
+
+    /// `(monitor, workspace)` of the focused group, or where the client last
+    /// reported the user to be when no agent window is focused.
+    fn current_location(&self) -> Option<(u32, u32)> {
+        let focused = self.focused_group.as_deref().and_then(|group| {
+            self.sessions
                 .iter()
-                .filter(|(_, info)| info.state == *priority_state)
-                .map(|(s, _)| s)
-                .collect();
+                .filter(|(_, info)| info.group == group)
+                .filter_map(|(s, _)| self.workspaces.get(window_key(s)))
+                .map(|&(ws, mon)| (mon, ws))
+                .min()
+        });
+        focused.or_else(|| self.location.as_ref().map(|l| (l.monitor, l.workspace)))
+    }
+
+    pub fn render_seq(&self) -> u64 {
+        self.render_seq
+    }
+
+    pub fn next_render_seq(&mut self) -> u64 {
+        self.render_seq += 1;
+        self.render_seq
+    }
+
+    /// Changes since the last call. While do-not-disturb is on they are
+    /// held back for the summary instead.
+    pub fn take_changes(&mut self) -> Vec<SessionChange> {
+        if self.dnd.is_some() {
+            self.dnd_held.append(&mut self.changes);
+        }
+        std::mem::take(&mut self.changes)
+    }
+
+    pub fn track_lifecycle_subscriber(&mut self, subscribed: bool) {
+        if subscribed {
+            self.lifecycle_subscribers += 1;
+        } else {
+            self.lifecycle_subscribers = self.lifecycle_subscribers.saturating_sub(1);
+        }
+    }
+
+    pub fn has_lifecycle_subscribers(&self) -> bool {
+        self.lifecycle_subscribers > 0
+    }
+
+    pub fn to_snapshot(&self) -> Snapshot {
+        let mut sessions: Vec<PersistedSession> = self
+            .sessions
+            .iter()
+            .map(|(session, info)| PersistedSession {
+                session: session.clone(),
+                state: info.state,
+                tool: info.tool.clone(),
+                agent_type: info.agent_type.to_string(),
+                state_since: info.state_since,
+                last_activity_at: unix_millis(info.last_activity),
+                meta: info.meta.clone(),
+                history: info.history.iter().cloned().collect(),
+                last_seq: info.last_seq,
+                parent_session: info.parent.clone(),
+                group: info.explicit_group.clone(),
+                group_label: info.explicit_label.clone(),
+            })
+            .collect();
+        sessions.sort_by(|a, b| a.session.cmp(&b.session));
+
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            saved_at: now_millis(),
+            sessions,
+            workspaces: self.workspaces.clone(),
+            awaiting_queue: self.awaiting_queue.clone(),
+            auto_focus_enabled: self.auto_focus_enabled,
+            focus_delay_ms: self.focus_delay_ms,
+            dnd_manual: self.dnd_manual.is_some(),
+            dnd_until: self.dnd_manual.flatten(),
+            quiet_overridden: self.quiet_overridden,
+        }
+    }
 
-            if matching.is_empty() {
+    /// Loads a snapshot taken by a previous daemon. Ended sessions and those
+    /// whose agent process is gone are dropped; the rest keep their age so
+    /// the usual stale and cleanup rules keep applying. Returns how many
+    /// sessions were restored.
+    pub fn restore(&mut self, snapshot: Snapshot) -> usize {
+        let now = Instant::now();
+        let wall = now_millis();
+
+        for p in snapshot.sessions {
+            if p.state == AgentState::Ended || !process_alive(&p.meta) {
                 continue;
             }
+            let age = Duration::from_millis(wall.saturating_sub(p.last_activity_at));
+            let mut info = SessionInfo {
+                last_activity: now.checked_sub(age).unwrap_or(now),
+                state_since: p.state_since,
+                meta: p.meta,
+                history: p.history.into(),
+                last_seq: p.last_seq,
+                parent: p.parent_session,
+                explicit_group: p.group,
+                explicit_label: p.group_label,
+                ..SessionInfo::new(&p.session, p.state, p.tool, p.agent_type.into())
+            };
+            info.regroup(&p.session, &self.config);
+            self.sessions.insert(p.session, info);
+        }
 
-            matching.sort_by(|a, b| {
-                let (ws_a, mon_a) = self.get_placement(Self::get_group(a));
-                let (ws_b, mon_b) = self.get_placement(Self::get_group(b));
-                mon_a.cmp(&mon_b).then(ws_a.cmp(&ws_b)).then_with(|| a.cmp(b))
-            });
-
-            self.last_focus_index = (self.last_focus_index + 1) % matching.len();
-            return Some(matching[self.last_focus_index].clone());
+        self.workspaces = snapshot.workspaces;
+        self.awaiting_queue = snapshot
+            .awaiting_queue
+            .into_iter()
+            .filter(|s| self.sessions.get(s).is_some_and(|i| i.state == AgentState::Awaiting))
+            .collect();
+        self.auto_focus_enabled = snapshot.auto_focus_enabled;
+        self.focus_delay_ms = snapshot.focus_delay_ms;
+        // An expiry that passed while the daemon was down ends it now.
+        if snapshot.dnd_manual && snapshot.dnd_until.is_none_or(|until| until > wall) {
+            self.dnd_manual = Some(snapshot.dnd_until);
         }
-        None
+        self.quiet_overridden = snapshot.quiet_overridden;
+        self.sessions.len()
     }
 
-    fn get_group(session: &str) -> &str {
-        session.split('#').next().unwrap_or(session)
+    fn record(&mut self, session: &str, previous: Option<AgentState>, state: Option<AgentState>) {
+        self.changes.push(SessionChange {
+            session: session.to_string(),
+            previous,
+            state,
+        });
     }
 
     #[cfg(feature = "test-helpers")]
@@ -302,6 +1084,26 @@ impl StateManager {
         }
     }
 
+    /// Moves recorded auto-focus switches and the last return `secs` into
+    /// the past.
+    #[cfg(feature = "test-helpers")]
+    pub fn age_auto_focus(&mut self, secs: u64) {
+        let by = Duration::from_secs(secs);
+        for t in self.auto_focus_switches.iter_mut() {
+            *t -= by;
+        }
+        if let Some(t) = &mut self.returned_at {
+            *t -= by;
+        }
+    }
+
+    #[cfg(feature = "test-helpers")]
+    pub fn force_dnd_expiry(&mut self) {
+        if let Some(Some(until)) = &mut self.dnd_manual {
+            *until = 0;
+        }
+    }
+
     #[cfg(feature = "test-helpers")]
     pub fn force_stale_session(&mut self, session: &str) {
         if let Some(info) = self.sessions.get_mut(session) {
@@ -310,6 +1112,87 @@ impl StateManager {
     }
 }
 
+/// Folds a run of changes into one per session, in order of first change,
+/// dropping sessions that ended up where they started.
+fn net_changes(held: Vec<SessionChange>) -> Vec<DndChange> {
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut net: Vec<DndChange> = Vec::new();
+    for change in held {
+        match index.get(&change.session) {
+            Some(&i) => net[i].state = change.state,
+            None => {
+                index.insert(change.session.clone(), net.len());
+                net.push(DndChange { session: change.session, previous: change.previous, state: change.state });
+            }
+        }
+    }
+    net.retain(|c| c.previous != c.state);
+    net
+}
+
+/// Orders sessions within a focus tier: agent-type priority, then monitor,
+/// workspace and id.
+type FocusKey = (Reverse<i32>, u32, u32, String);
+
+/// The part of a session id before `#`. Hooks put it in the terminal title,
+/// and the extension reports workspaces by it.
+fn window_key(session: &str) -> &str {
+    session.split('#').next().unwrap_or(session)
+}
+
+fn basename(path: &str) -> &str {
+    path.trim_end_matches('/').rsplit('/').next().filter(|b| !b.is_empty()).unwrap_or(path)
+}
+
+/// Rank used to aggregate subagent states: a waiting child outranks
+/// everything, finished work outranks idle.
+fn urgency(state: AgentState) -> u8 {
+    match state {
+        AgentState::Awaiting => 5,
+        AgentState::Working => 4,
+        AgentState::Processing => 3,
+        AgentState::Completed => 2,
+        AgentState::Started => 1,
+        AgentState::Ended => 0,
+    }
+}
+
+/// Whether the process a session reported is still running. Sessions
+/// without a `pid` are always considered alive.
+pub fn process_alive(meta: &SessionMetadata) -> bool {
+    let Some(pid) = meta.pid else {
+        return true;
+    };
+    let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid)) else {
+        return false;
+    };
+    // `comm` may contain spaces and parentheses; fields resume after the last ')'.
+    let mut fields = stat.rsplit_once(')').map_or("", |(_, rest)| rest).split_whitespace();
+    if fields.next() == Some("Z") {
+        return false;
+    }
+    match (meta.pid_start_time, fields.nth(18).and_then(|f| f.parse::<u64>().ok())) {
+        (Some(expected), Some(actual)) => expected == actual,
+        _ => true,
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn unix_millis(at: Instant) -> u64 {
+    let age = Instant::now().saturating_duration_since(at);
+    SystemTime::now()
+        .checked_sub(age)
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
 impl Default for StateManager {
     fn default() -> Self {
         Self::new()
@@ -1,26 +1,77 @@
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::{broadcast, Mutex, Notify};
-use tracing::debug;
+use tracing::{debug, info};
 
+use crate::config;
 use crate::protocol::OutgoingMessage;
-use crate::state::StateManager;
+use crate::publish;
+use crate::state::{AutoFocusEvent, StateManager};
 
 pub fn spawn_cleanup(state: Arc<Mutex<StateManager>>, tx: broadcast::Sender<OutgoingMessage>) {
     tokio::spawn(async move {
-        let mut interval = tokio::time::interval(Duration::from_secs(5));
         loop {
-            interval.tick().await;
+            let period = state.lock().await.config().cleanup_interval();
+            tokio::time::sleep(period).await;
 
-            let render = {
-                let mut s = state.lock().await;
-                if !s.cleanup_ended() {
-                    continue;
-                }
-                OutgoingMessage::Render { agents: s.get_render_data() }
+            let mut s = state.lock().await;
+            if !s.cleanup_ended() {
+                continue;
+            }
+            publish::changes(&mut s, &tx);
+            publish::render(&mut s, &tx);
+        }
+    });
+}
+
+/// Ends sessions whose agent process died without sending `SessionEnd`.
+pub fn spawn_liveness(
+    state: Arc<Mutex<StateManager>>,
+    tx: broadcast::Sender<OutgoingMessage>,
+    notify: Arc<Notify>,
+) {
+    tokio::spawn(async move {
+        loop {
+            let period = state.lock().await.config().liveness_interval();
+            tokio::time::sleep(period).await;
+
+            let mut s = state.lock().await;
+            let Some(event) = s.reap_dead() else {
+                continue;
             };
-            let _ = tx.send(render);
+            publish::changes(&mut s, &tx);
+            publish::render(&mut s, &tx);
+            match event {
+                AutoFocusEvent::Trigger => notify.notify_one(),
+                AutoFocusEvent::QueueEmpty => publish::return_workspace(&mut s, &tx),
+                AutoFocusEvent::None => {}
+            }
+        }
+    });
+}
+
+/// Starts and ends do-not-disturb as quiet hours begin and end, and when a
+/// manual one expires.
+pub fn spawn_dnd(
+    state: Arc<Mutex<StateManager>>,
+    tx: broadcast::Sender<OutgoingMessage>,
+    notify: Arc<Notify>,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(DND_TICK);
+        loop {
+            interval.tick().await;
+
+            let (day, time) = config::local_time();
+            let mut s = state.lock().await;
+            let ended = s.refresh_dnd(day, time);
+            publish::changes(&mut s, &tx);
+            if ended {
+                notify.notify_one();
+            }
         }
     });
 }
@@ -35,9 +86,10 @@ pub fn spawn_auto_focus(
             notify.notified().await;
 
             loop {
-                let (should, delay) = {
+                let (should, wait) = {
                     let s = state.lock().await;
-                    (s.should_auto_focus(), s.focus_delay_ms())
+                    let delay = Duration::from_millis(s.focus_delay_ms());
+                    (s.should_auto_focus(), s.auto_focus_hold().map_or(delay, |hold| hold.max(delay)))
                 };
 
                 if !should {
@@ -45,9 +97,13 @@ pub fn spawn_auto_focus(
                 }
 
                 tokio::select! {
-                    _ = tokio::time::sleep(Duration::from_millis(delay)) => {
+                    _ = tokio::time::sleep(wait) => {
                         let result = {
                             let mut s = state.lock().await;
+                            if let Some(hold) = s.auto_focus_hold() {
+                                debug!("Auto-focus held off for {:?}", hold);
+                                continue;
+                            }
                             if s.should_auto_focus() {
                                 s.next_awaiting().map(|session| {
                                     let agent_type = s.get_agent_type(&session);
@@ -69,3 +125,39 @@ pub fn spawn_auto_focus(
         }
     });
 }
+
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const DND_TICK: Duration = Duration::from_secs(1);
+
+pub fn spawn_config_watcher(
+    state: Arc<Mutex<StateManager>>,
+    tx: broadcast::Sender<OutgoingMessage>,
+    path: PathBuf,
+) -> std::io::Result<()> {
+    let mut sighup = signal(SignalKind::hangup())?;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CONFIG_POLL_INTERVAL);
+        let mut last_modified = modified_at(&path);
+        loop {
+            tokio::select! {
+                _ = sighup.recv() => info!("SIGHUP, reloading config"),
+                _ = interval.tick() => {
+                    let current = modified_at(&path);
+                    if current == last_modified {
+                        continue;
+                    }
+                    last_modified = current;
+                }
+            }
+            if config::reload(&path, &state).await {
+                publish::render(&mut *state.lock().await, &tx);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
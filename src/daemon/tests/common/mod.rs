@@ -3,7 +3,7 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use argus_agenticus::handler::Effects;
-use argus_agenticus::protocol::{AgentState, IncomingMessage, OutgoingMessage};
+use argus_agenticus::protocol::{AgentState, ClientKind, IncomingMessage, OutgoingMessage, Topic};
 use argus_agenticus::state::{AutoFocusEvent, StateManager};
 
 pub fn to_s(v: &str) -> String {
@@ -25,6 +25,45 @@ pub fn msg_state(session: &str, state: AgentState) -> IncomingMessage {
         state,
         tool: to_s("bash"),
         agent_type: to_s("claude"),
+        event: None,
+        seq: None,
+        parent_session: None,
+        group: None,
+        group_label: None,
+        meta: Default::default(),
+    }
+}
+
+pub fn msg_state_seq(session: &str, state: AgentState, seq: u64) -> IncomingMessage {
+    let mut msg = msg_state(session, state);
+    if let IncomingMessage::State { seq: s, .. } = &mut msg {
+        *s = Some(seq);
+    }
+    msg
+}
+
+pub fn msg_subagent_state(session: &str, parent: &str, state: AgentState) -> IncomingMessage {
+    let mut msg = msg_state(session, state);
+    if let IncomingMessage::State { parent_session, .. } = &mut msg {
+        *parent_session = Some(to_s(parent));
+    }
+    msg
+}
+
+pub fn msg_grouped_state(session: &str, group: &str, label: Option<&str>) -> IncomingMessage {
+    let mut msg = msg_state(session, AgentState::Working);
+    if let IncomingMessage::State { group: g, group_label, .. } = &mut msg {
+        *g = Some(to_s(group));
+        *group_label = label.map(to_s);
+    }
+    msg
+}
+
+pub fn msg_hello(version: u32, client: ClientKind) -> IncomingMessage {
+    IncomingMessage::Hello {
+        protocol_version: version,
+        client,
+        capabilities: Vec::new(),
     }
 }
 
@@ -32,6 +71,10 @@ pub fn msg_window_focus(title: &str) -> IncomingMessage {
     IncomingMessage::WindowFocus { title: to_s(title), agent_type: String::new() }
 }
 
+pub fn msg_location(ws: u32, window: &str) -> IncomingMessage {
+    IncomingMessage::Location { workspace: ws, monitor: 0, window: Some(to_s(window)) }
+}
+
 pub fn msg_workspace(session: &str, ws: u32) -> IncomingMessage {
     IncomingMessage::SessionWorkspace {
         session: to_s(session),
@@ -63,6 +106,23 @@ pub fn msg_window_closed(session: &str) -> IncomingMessage {
     IncomingMessage::WindowClosed { session: to_s(session) }
 }
 
+pub fn msg_list_sessions(state: Option<AgentState>) -> IncomingMessage {
+    IncomingMessage::ListSessions { state }
+}
+
+pub fn msg_get_session(session: &str) -> IncomingMessage {
+    IncomingMessage::GetSession { session: to_s(session) }
+}
+
+pub fn msg_subscribe(topics: &[Topic]) -> IncomingMessage {
+    IncomingMessage::Subscribe { topics: topics.to_vec() }
+}
+
+#[allow(dead_code)]
+pub fn msg_batch(messages: Vec<IncomingMessage>) -> IncomingMessage {
+    IncomingMessage::Batch { messages }
+}
+
 pub fn should_broadcast(fx: &Effects) {
     assert!(fx.broadcast_render, "expected broadcast_render=true");
 }
@@ -72,16 +132,23 @@ pub fn should_not_broadcast(fx: &Effects) {
 }
 
 pub fn should_reply_focus(fx: &Effects, session: &str) {
-    match &fx.reply {
-        Some(OutgoingMessage::Focus { session: s, .. }) => {
+    match fx.replies.as_slice() {
+        [OutgoingMessage::Focus { session: s, .. }] => {
             assert_eq!(s, session, "expected Focus for '{session}', got '{s}'");
         }
         other => panic!("expected Focus reply for '{session}', got {other:?}"),
     }
 }
 
+pub fn should_reply_welcome(fx: &Effects) {
+    match fx.replies.as_slice() {
+        [OutgoingMessage::Welcome { .. }] => {}
+        other => panic!("expected Welcome reply, got {other:?}"),
+    }
+}
+
 pub fn should_have_no_reply(fx: &Effects) {
-    assert!(fx.reply.is_none(), "expected no reply, got {:?}", fx.reply);
+    assert!(fx.replies.is_empty(), "expected no reply, got {:?}", fx.replies);
 }
 
 pub fn should_mark_extension(fx: &Effects) {
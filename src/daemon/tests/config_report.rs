@@ -0,0 +1,494 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+
+use argus_agenticus::config::{self, ClockTime, Config, ConfigError, Weekday};
+use argus_agenticus::protocol::{AgentState, DndReason, Location, SessionMetadata};
+use argus_agenticus::state::{DndEvent, StateManager};
+
+fn temp_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join("argus-test");
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(format!("{}-{}.toml", name, std::process::id()))
+}
+
+fn invalid(text: &str) -> String {
+    match Config::parse(text) {
+        Err(ConfigError::Invalid(msg)) => msg,
+        other => panic!("expected Invalid for {text:?}, got {other:?}"),
+    }
+}
+
+#[test]
+fn defaults_match_previous_constants() {
+    let c = Config::default();
+    assert_eq!(c.ended_hide_delay(), Duration::from_secs(10));
+    assert_eq!(c.stale_timeout(), Duration::from_secs(30));
+    assert_eq!(c.cleanup_interval(), Duration::from_secs(5));
+    assert_eq!(c.focus_priorities, vec![AgentState::Awaiting, AgentState::Completed, AgentState::Started]);
+    assert_eq!(c.channel_capacity, 64);
+    assert_eq!(c.max_line_length, 65_536);
+}
+
+#[test]
+fn empty_file_is_defaults() {
+    assert_eq!(Config::parse("").unwrap(), Config::default());
+}
+
+#[test]
+fn parse_full_file() {
+    let c = Config::parse(
+        r#"
+        ended_hide_delay_secs = 60
+        stale_timeout_secs = 120
+        cleanup_interval_secs = 2
+        focus_priorities = ["awaiting", "completed"]
+        channel_capacity = 256
+        max_line_length = 4096
+        "#,
+    )
+    .unwrap();
+    assert_eq!(c.ended_hide_delay_secs, 60);
+    assert_eq!(c.stale_timeout_secs, 120);
+    assert_eq!(c.cleanup_interval_secs, 2);
+    assert_eq!(c.focus_priorities, vec![AgentState::Awaiting, AgentState::Completed]);
+    assert_eq!(c.channel_capacity, 256);
+    assert_eq!(c.max_line_length, 4096);
+}
+
+#[test]
+fn parse_rejects_unknown_keys_and_states() {
+    assert!(matches!(Config::parse("stale_timeout = 3"), Err(ConfigError::Parse(_))));
+    assert!(matches!(Config::parse(r#"focus_priorities = ["red"]"#), Err(ConfigError::Parse(_))));
+    assert!(matches!(Config::parse("stale_timeout_secs = -1"), Err(ConfigError::Parse(_))));
+}
+
+#[test]
+fn validate_rejects_bad_values() {
+    assert!(invalid("stale_timeout_secs = 0").contains("stale_timeout_secs"));
+    assert!(invalid("cleanup_interval_secs = 0").contains("cleanup_interval_secs"));
+    assert!(invalid("focus_priorities = []").contains("focus_priorities"));
+    assert!(invalid(r#"focus_priorities = ["awaiting", "started", "awaiting"]"#).contains("twice"));
+    assert!(invalid("channel_capacity = 0").contains("channel_capacity"));
+    assert!(invalid("max_line_length = 10").contains("max_line_length"));
+}
+
+#[test]
+fn load_missing_file_is_defaults() {
+    let path = temp_file("missing");
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(config::load(&path).unwrap(), Config::default());
+}
+
+#[tokio::test]
+async fn reload_applies_valid_and_keeps_invalid() {
+    let path = temp_file("reload");
+    let state = Mutex::new(StateManager::new());
+
+    std::fs::write(&path, "stale_timeout_secs = 90\n").unwrap();
+    config::reload(&path, &state).await;
+    assert_eq!(state.lock().await.config().stale_timeout_secs, 90);
+
+    std::fs::write(&path, "stale_timeout_secs = \"soon\"\n").unwrap();
+    config::reload(&path, &state).await;
+    assert_eq!(state.lock().await.config().stale_timeout_secs, 90, "invalid file keeps running config");
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn reload_regroups_and_follows_focus() {
+    let path = temp_file("regroup");
+    let state = Mutex::new(StateManager::new());
+    {
+        let mut s = state.lock().await;
+        for session in ["z2#1", "z3#1"] {
+            s.update_state(session.into(), AgentState::Working, String::new(), "claude".into());
+            s.update_metadata(
+                session,
+                SessionMetadata { repository: Some("/home/u/src/argus".into()), ..Default::default() },
+            );
+        }
+        s.update_window_focus("z2", None);
+    }
+
+    std::fs::write(&path, "stale_timeout_secs = 90
+").unwrap();
+    assert!(!config::reload(&path, &state).await, "same groups need no render");
+
+    std::fs::write(&path, "group_by = \"repository\"\n").unwrap();
+    assert!(config::reload(&path, &state).await);
+    let mut s = state.lock().await;
+    assert!(s.get_render_data().iter().all(|a| a.group_key == "/home/u/src/argus" && a.focused));
+    s.update_state("z3#1".into(), AgentState::Completed, String::new(), "claude".into());
+    assert_eq!(s.get_render_data()[1].state, AgentState::Started, "focused group demotes completed");
+    drop(s);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn stale_timeout_from_config() {
+    let mut sm = StateManager::with_config(Config { stale_timeout_secs: 600, ..Config::default() });
+    sm.update_state("p#c-1".into(), AgentState::Started, String::new(), "cursor".into());
+    sm.force_stale_session("p#c-1");
+    sm.cleanup_ended();
+    assert_eq!(sm.get_render_data()[0].state, AgentState::Started, "120s idle is within a 600s timeout");
+
+    sm.set_config(Config { stale_timeout_secs: 60, ..Config::default() });
+    sm.cleanup_ended();
+    assert_eq!(sm.get_render_data()[0].state, AgentState::Ended);
+}
+
+#[test]
+fn focus_priorities_from_config() {
+    let mut sm = StateManager::with_config(Config {
+        focus_priorities: vec![AgentState::Working],
+        ..Config::default()
+    });
+    sm.update_state("a#1".into(), AgentState::Awaiting, String::new(), "claude".into());
+    sm.update_state("b#1".into(), AgentState::Working, String::new(), "claude".into());
+    assert_eq!(sm.focus_next().as_deref(), Some("b#1"));
+}
+
+#[test]
+fn agent_policy_defaults_match_previous_behavior() {
+    let c = Config::default();
+    let claude = c.policy("claude");
+    assert!(claude.session_end_reliable);
+    assert_eq!(claude.ended_hide_delay, Duration::from_secs(10));
+    let other = c.policy("cursor");
+    assert!(!other.session_end_reliable);
+    assert_eq!(other.stale_timeout, Duration::from_secs(30));
+    assert_eq!(other.focus_priority, 0);
+}
+
+#[test]
+fn agent_policy_overrides_fall_back_per_key() {
+    let c = Config::parse(
+        r#"
+        stale_timeout_secs = 45
+
+        [agents.claude]
+        ended_hide_delay_secs = 2
+
+        [agents.aider]
+        session_end_reliable = true
+        focus_priority = 5
+        "#,
+    )
+    .unwrap();
+    let claude = c.policy("claude");
+    assert!(claude.session_end_reliable, "built-in key kept when not overridden");
+    assert_eq!(claude.ended_hide_delay, Duration::from_secs(2));
+    let aider = c.policy("aider");
+    assert!(aider.session_end_reliable);
+    assert_eq!(aider.focus_priority, 5);
+    assert_eq!(aider.stale_timeout, Duration::from_secs(45));
+
+    assert!(matches!(Config::parse("[agents.x]\nstale = 1"), Err(ConfigError::Parse(_))));
+    assert!(invalid("[agents.x]\nstale_timeout_secs = 0").contains("agents.x"));
+}
+
+#[test]
+fn agent_policy_drives_cleanup() {
+    let mut sm = StateManager::with_config(
+        Config::parse("[agents.claude]\nsession_end_reliable = false\n\n[agents.aider]\nsession_end_reliable = true")
+            .unwrap(),
+    );
+    sm.update_state("p#c-1".into(), AgentState::Started, String::new(), "claude".into());
+    sm.update_state("p#a-1".into(), AgentState::Started, String::new(), "aider".into());
+    sm.force_stale_session("p#c-1");
+    sm.force_stale_session("p#a-1");
+    sm.cleanup_ended();
+    let states: Vec<_> = sm.get_render_data().into_iter().map(|a| (a.session, a.state)).collect();
+    assert!(states.contains(&("p#c-1".to_string(), AgentState::Ended)));
+    assert!(states.contains(&("p#a-1".to_string(), AgentState::Started)));
+}
+
+#[test]
+fn agent_focus_priority_orders_within_a_state() {
+    let mut sm = StateManager::with_config(Config::parse("[agents.aider]\nfocus_priority = 10").unwrap());
+    sm.update_state("a#1".into(), AgentState::Awaiting, String::new(), "claude".into());
+    sm.update_state("b#1".into(), AgentState::Awaiting, String::new(), "aider".into());
+    let cycle: Vec<_> = (0..2).filter_map(|_| sm.focus_next()).collect();
+    assert_eq!(cycle, ["b#1", "a#1"]);
+    assert_eq!(sm.next_awaiting().as_deref(), Some("b#1"));
+}
+
+#[test]
+fn group_by_and_rules() {
+    let mut sm = StateManager::with_config(
+        Config::parse(
+            r#"
+            group_by = "repository"
+
+            [[group_rules]]
+            cwd_prefix = "/home/u/work/"
+            group = "work"
+            label = "Work"
+            "#,
+        )
+        .unwrap(),
+    );
+    let meta = |cwd: &str, repo: Option<&str>| SessionMetadata {
+        cwd: Some(cwd.to_string()),
+        repository: repo.map(str::to_string),
+        ..Default::default()
+    };
+    for s in ["z1#1", "z2#1", "z3#1", "z4#1"] {
+        sm.update_state(s.into(), AgentState::Working, String::new(), "claude".into());
+    }
+    sm.update_metadata("z1#1", meta("/home/u/work/api", Some("/home/u/work/api")));
+    sm.update_metadata("z2#1", meta("/home/u/src/argus/daemon", Some("/home/u/src/argus")));
+    sm.update_metadata("z3#1", meta("/home/u/src/argus", Some("/home/u/src/argus")));
+    sm.update_metadata("z4#1", meta("/tmp", None));
+
+    let groups: Vec<(String, String, Option<String>)> =
+        sm.get_render_data().into_iter().map(|a| (a.session, a.group_key, a.group_label)).collect();
+    assert_eq!(
+        groups,
+        [
+            ("z2#1".into(), "/home/u/src/argus".into(), Some("argus".into())),
+            ("z3#1".into(), "/home/u/src/argus".into(), Some("argus".into())),
+            ("z1#1".into(), "work".into(), Some("Work".into())),
+            ("z4#1".into(), "z4".into(), None),
+        ]
+    );
+
+    sm.set_config(Config::default());
+    assert!(sm.get_render_data().iter().all(|a| a.group_key == a.session.split('#').next().unwrap()));
+
+    assert!(invalid("[[group_rules]]\ngroup = \"x\"").contains("group_rules[0]"));
+    assert!(invalid("[[group_rules]]\nagent_type = \"claude\"\ngroup = \"\"").contains("group"));
+    assert!(matches!(Config::parse(r#"group_by = "moon""#), Err(ConfigError::Parse(_))));
+}
+
+#[test]
+fn focus_scope_limits_cycle_to_focused_location() {
+    let mut sm = StateManager::with_config(Config::parse(r#"focus_scope = "workspace""#).unwrap());
+    for (name, ws, mon) in [("a", 1, 0), ("b", 2, 0), ("c", 1, 0), ("d", 1, 1)] {
+        sm.update_state(format!("{name}#1"), AgentState::Started, String::new(), "claude".into());
+        sm.update_workspace(name, ws, mon);
+    }
+    let cycle: Vec<_> = (0..4).filter_map(|_| sm.focus_next()).collect();
+    assert_eq!(cycle, ["a#1", "c#1", "b#1", "d#1"], "no focused window, no restriction");
+
+    sm.update_window_focus("a", None);
+    let cycle: Vec<_> = (0..3).filter_map(|_| sm.focus_next()).collect();
+    assert_eq!(cycle, ["a#1", "c#1", "a#1"]);
+
+    sm.set_config(Config::parse(r#"focus_scope = "monitor""#).unwrap());
+    let cycle: Vec<_> = (0..3).filter_map(|_| sm.focus_next()).collect();
+    assert_eq!(cycle, ["c#1", "b#1", "a#1"], "cursor carries over, now across the monitor");
+
+    sm.update_window_focus("d", None);
+    sm.set_config(Config::parse(r#"focus_scope = "workspace""#).unwrap());
+    assert_eq!(sm.focus_next().as_deref(), Some("d#1"));
+}
+
+/// Four awaiting sessions queued in the order a, b, c, d. The focused
+/// window is `home` on monitor 0, workspace 3.
+fn awaiting_spread(config: &str) -> StateManager {
+    let mut sm = StateManager::with_config(Config::parse(config).unwrap());
+    sm.update_state("home#1".into(), AgentState::Started, String::new(), "claude".into());
+    sm.update_workspace("home", 3, 0);
+    sm.update_window_focus("home", None);
+    for (name, ws, mon) in [("a", 1, 1), ("b", 1, 0), ("c", 3, 0), ("d", 5, 1)] {
+        sm.update_state(format!("{name}#1"), AgentState::Awaiting, String::new(), "claude".into());
+        sm.update_workspace(name, ws, mon);
+    }
+    sm
+}
+
+#[test]
+fn auto_focus_policies() {
+    for (policy, expected) in [
+        ("fifo", "a#1"),
+        ("longest_waiting", "a#1"),
+        ("current_monitor", "b#1"),
+        ("nearest_workspace", "c#1"),
+        ("weighted", "c#1"),
+    ] {
+        let mut sm = awaiting_spread(&format!("auto_focus_policy = \"{policy}\""));
+        assert_eq!(sm.next_awaiting().as_deref(), Some(expected), "{policy}");
+    }
+
+    let mut sm = awaiting_spread(
+        r#"
+        auto_focus_policy = "weighted"
+        project_priorities = { d = 2 }
+        "#,
+    );
+    assert_eq!(sm.next_awaiting().as_deref(), Some("d#1"), "project priority outweighs location");
+
+    let mut sm = awaiting_spread(
+        r#"
+        auto_focus_policy = "weighted"
+        project_priorities = { d = 1 }
+        auto_focus_weights = { project = 1 }
+        "#,
+    );
+    assert_eq!(sm.next_awaiting().as_deref(), Some("c#1"), "unset weights keep their defaults");
+
+    assert!(matches!(Config::parse(r#"auto_focus_policy = "random""#), Err(ConfigError::Parse(_))));
+    assert!(matches!(Config::parse("auto_focus_weights = { recency = 1 }"), Err(ConfigError::Parse(_))));
+}
+
+#[test]
+fn auto_focus_dwell_and_cooldown() {
+    let mut sm = awaiting_spread("auto_focus_dwell_secs = 20");
+    assert_eq!(sm.auto_focus_hold(), None);
+    assert_eq!(sm.next_awaiting().as_deref(), Some("a#1"));
+    let hold = sm.auto_focus_hold().expect("dwell after a switch");
+    assert!(hold > Duration::from_secs(19) && hold <= Duration::from_secs(20), "{hold:?}");
+    sm.age_auto_focus(20);
+    assert_eq!(sm.auto_focus_hold(), None);
+
+    let mut sm = awaiting_spread("auto_focus_return_cooldown_secs = 30");
+    sm.update_window_focus("a", None);
+    assert_eq!(sm.auto_focus_hold(), None, "moving between agent windows is not a return");
+    sm.update_window_focus("Firefox", None);
+    assert!(sm.auto_focus_hold().is_some_and(|h| h > Duration::from_secs(29)));
+    sm.age_auto_focus(30);
+    assert_eq!(sm.auto_focus_hold(), None);
+}
+
+#[test]
+fn auto_focus_max_per_minute() {
+    let mut sm = awaiting_spread("auto_focus_max_per_minute = 2");
+    sm.next_awaiting();
+    assert_eq!(sm.auto_focus_hold(), None);
+    sm.age_auto_focus(10);
+    sm.next_awaiting();
+    let hold = sm.auto_focus_hold().expect("cap reached");
+    assert!(hold > Duration::from_secs(49) && hold <= Duration::from_secs(50), "{hold:?}");
+    sm.age_auto_focus(50);
+    assert_eq!(sm.auto_focus_hold(), None, "the oldest switch left the window");
+
+    let mut unlimited = awaiting_spread("");
+    for _ in 0..10 {
+        unlimited.next_awaiting();
+    }
+    assert_eq!(unlimited.auto_focus_hold(), None);
+}
+
+#[test]
+fn location_used_outside_agent_windows() {
+    let away = |config: &str| {
+        let mut sm = awaiting_spread(config);
+        sm.update_window_focus("Firefox", None);
+        sm.set_location(Location { workspace: 5, monitor: 1, window: None });
+        sm
+    };
+    assert_eq!(away(r#"auto_focus_policy = "current_monitor""#).next_awaiting().as_deref(), Some("a#1"));
+    assert_eq!(away(r#"auto_focus_policy = "nearest_workspace""#).next_awaiting().as_deref(), Some("d#1"));
+    assert_eq!(away(r#"auto_focus_policy = "weighted""#).next_awaiting().as_deref(), Some("d#1"));
+    assert_eq!(away(r#"focus_scope = "workspace""#).focus_next().as_deref(), Some("d#1"));
+}
+
+#[test]
+fn weighted_extreme_weights_saturate() {
+    let mut sm = awaiting_spread(
+        r#"
+        auto_focus_policy = "weighted"
+        project_priorities = { d = 2, a = -2 }
+        auto_focus_weights = { project = 9223372036854775807, same_workspace = -9223372036854775808 }
+        "#,
+    );
+    assert_eq!(sm.next_awaiting().as_deref(), Some("d#1"));
+}
+
+#[test]
+fn longest_waiting_ignores_queue_order() {
+    let mut snapshot = awaiting_spread("").to_snapshot();
+    snapshot.awaiting_queue.reverse();
+    for p in &mut snapshot.sessions {
+        if p.session == "b#1" {
+            p.state_since -= 60_000;
+        }
+    }
+
+    let mut fifo = StateManager::new();
+    fifo.restore(snapshot.clone());
+    assert_eq!(fifo.next_awaiting().as_deref(), Some("d#1"));
+
+    let mut longest = StateManager::with_config(Config::parse(r#"auto_focus_policy = "longest_waiting""#).unwrap());
+    longest.restore(snapshot);
+    assert_eq!(longest.next_awaiting().as_deref(), Some("b#1"));
+}
+
+fn at(hour: u16, minute: u16) -> ClockTime {
+    ClockTime::new(hour, minute).unwrap()
+}
+
+#[test]
+fn quiet_hours() {
+    let config = Config::parse(
+        r#"
+        [[quiet_hours]]
+        days = ["mon", "tue", "wed", "thu", "fri"]
+        start = "12:00"
+        end = "13:30"
+
+        [[quiet_hours]]
+        days = ["fri"]
+        start = "22:00"
+        end = "7:00"
+        "#,
+    )
+    .unwrap();
+    assert!(config.in_quiet_hours(Weekday::Mon, at(12, 0)));
+    assert!(!config.in_quiet_hours(Weekday::Mon, at(13, 30)), "end is exclusive");
+    assert!(!config.in_quiet_hours(Weekday::Sat, at(12, 15)));
+    assert!(config.in_quiet_hours(Weekday::Fri, at(23, 0)));
+    assert!(config.in_quiet_hours(Weekday::Sat, at(6, 59)), "overnight runs past midnight");
+    assert!(!config.in_quiet_hours(Weekday::Fri, at(6, 59)), "and belongs to its start day");
+    assert!(!config.in_quiet_hours(Weekday::Sat, at(22, 0)));
+
+    let every_day = Config::parse("quiet_hours = [{ start = \"00:00\", end = \"08:00\" }]").unwrap();
+    assert!(every_day.in_quiet_hours(Weekday::Sun, at(3, 0)));
+
+    for bad in ["24:00", "12:60", "noon", "12:5"] {
+        let text = format!("quiet_hours = [{{ start = \"{bad}\", end = \"13:00\" }}]");
+        assert!(matches!(Config::parse(&text), Err(ConfigError::Parse(_))), "{bad}");
+    }
+    assert!(invalid(r#"quiet_hours = [{ start = "09:00", end = "09:00" }]"#).contains("same time"));
+    assert!(invalid(r#"quiet_hours = [{ days = [], start = "09:00", end = "10:00" }]"#).contains("days"));
+}
+
+#[test]
+fn dnd_follows_quiet_hours() {
+    let mut sm = awaiting_spread(r#"quiet_hours = [{ start = "09:00", end = "10:00" }]"#);
+    sm.set_idle(true);
+    sm.set_auto_focus_config(true, 0);
+    assert!(!sm.refresh_dnd(Weekday::Mon, at(8, 59)));
+    assert!(sm.should_auto_focus());
+
+    assert!(!sm.refresh_dnd(Weekday::Mon, at(9, 0)));
+    assert!(!sm.should_auto_focus(), "no auto-focus during quiet hours");
+    assert_eq!(sm.take_dnd_events(), vec![DndEvent::Started { reason: DndReason::QuietHours, until: None }]);
+
+    sm.set_dnd(false, None);
+    assert!(sm.refresh_dnd(Weekday::Mon, at(9, 1)), "turning it off ends quiet hours early");
+    assert!(!sm.refresh_dnd(Weekday::Mon, at(9, 30)));
+    assert!(!sm.dnd_active(), "for the rest of the period");
+    sm.refresh_dnd(Weekday::Mon, at(10, 0));
+    sm.refresh_dnd(Weekday::Tue, at(9, 0));
+    assert!(sm.dnd_active(), "the next period applies again");
+
+    sm.set_dnd(true, Some(Duration::from_secs(45 * 60)));
+    sm.take_dnd_events();
+    sm.refresh_dnd(Weekday::Tue, at(9, 5));
+    let events = sm.take_dnd_events();
+    assert!(
+        matches!(events[..], [DndEvent::Started { reason: DndReason::Manual, until: Some(_) }]),
+        "an expiry set during quiet hours is announced: {events:?}"
+    );
+    sm.force_dnd_expiry();
+    assert!(!sm.refresh_dnd(Weekday::Tue, at(9, 6)), "quiet hours still apply after it expires");
+    sm.force_dnd_expiry();
+    assert!(sm.refresh_dnd(Weekday::Tue, at(10, 0)));
+}
+
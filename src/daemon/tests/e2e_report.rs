@@ -6,7 +6,11 @@ use tokio::net::UnixStream;
 use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::task::JoinHandle;
 
+use argus_agenticus::config::Config;
+use argus_agenticus::persist;
+use argus_agenticus::protocol::AgentState;
 use argus_agenticus::socket::SocketServer;
+use argus_agenticus::state::StateManager;
 
 struct TestServer {
     path: PathBuf,
@@ -15,6 +19,10 @@ struct TestServer {
 
 impl TestServer {
     async fn start(name: &str) -> Self {
+        Self::start_with(name, SocketServer::new).await
+    }
+
+    async fn start_with(name: &str, build: impl FnOnce(PathBuf) -> SocketServer) -> Self {
         let dir = std::env::temp_dir().join("argus-test");
         std::fs::create_dir_all(&dir).unwrap();
         let path = dir.join(format!(
@@ -29,7 +37,7 @@ impl TestServer {
 
         let _ = std::fs::remove_file(&path);
 
-        let server = SocketServer::new(path.clone());
+        let server = build(path.clone());
         let handle = tokio::spawn(async move { server.run().await });
 
         for _ in 0..50 {
@@ -105,6 +113,54 @@ async fn click_returns_focus() {
     srv.shutdown().await;
 }
 
+#[tokio::test]
+async fn hello_returns_welcome() {
+    let srv = TestServer::start("hello").await;
+    let mut c = srv.connect().await;
+
+    c.send(r#"{"type":"hello","protocol_version":1,"client":"cli","capabilities":[]}"#).await;
+    let resp = c.recv().await;
+
+    assert_eq!(resp["type"], "welcome");
+    assert_eq!(resp["protocol_version"], 1);
+    assert!(resp["daemon_version"].is_string());
+    assert!(resp["features"].is_array());
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn hello_outdated_refused_and_closed() {
+    let srv = TestServer::start("hello_refused").await;
+    let mut c = srv.connect().await;
+
+    c.send(r#"{"type":"hello","protocol_version":0,"client":"extension"}"#).await;
+    let resp = c.recv().await;
+    assert_eq!(resp["type"], "refused");
+    assert!(resp["reason"].as_str().unwrap().contains("minimum"));
+
+    let closed = c.recv_timeout(500).await;
+    assert!(closed.is_none(), "connection should be closed after refusal, got {closed:?}");
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn hello_extension_receives_broadcast() {
+    let srv = TestServer::start("hello_ext").await;
+    let mut ext = srv.connect().await;
+    let mut agent = srv.connect().await;
+
+    ext.send(r#"{"type":"hello","protocol_version":1,"client":"extension"}"#).await;
+    assert_eq!(ext.recv().await["type"], "welcome");
+
+    agent.send(r#"{"type":"state","session":"proj#1","state":"started","tool":"bash"}"#).await;
+    let resp = ext.recv().await;
+    assert_eq!(resp["type"], "render");
+
+    srv.shutdown().await;
+}
+
 #[tokio::test]
 async fn state_broadcasts_to_extension() {
     let srv = TestServer::start("broadcast").await;
@@ -158,6 +214,392 @@ async fn window_focus_makes_extension() {
     srv.shutdown().await;
 }
 
+#[tokio::test]
+async fn list_sessions_without_extension() {
+    let srv = TestServer::start("list_sessions").await;
+    let mut cli = srv.connect().await;
+    let mut agent = srv.connect().await;
+
+    agent.send(r#"{"type":"state","session":"proj#1","state":"awaiting","tool":"Bash"}"#).await;
+    agent.send(r#"{"type":"state","session":"proj#2","state":"working","tool":"Edit"}"#).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    cli.send(r#"{"type":"list_sessions","state":"awaiting"}"#).await;
+    let resp = cli.recv().await;
+    assert_eq!(resp["type"], "sessions");
+    let sessions = resp["sessions"].as_array().unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0]["session"], "proj#1");
+    assert_eq!(sessions[0]["tool"], "Bash");
+
+    agent.send(r#"{"type":"state","session":"proj#3","state":"started","tool":""}"#).await;
+    let resp = cli.recv_timeout(200).await;
+    assert!(resp.is_none(), "query client should not receive broadcast, got {resp:?}");
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn subscribe_render_sends_snapshot() {
+    let srv = TestServer::start("subscribe_snapshot").await;
+    let mut observer = srv.connect().await;
+    let mut agent = srv.connect().await;
+
+    agent.send(r#"{"type":"state","session":"proj#1","state":"awaiting","tool":"bash"}"#).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    observer.send(r#"{"type":"subscribe","topics":["render"]}"#).await;
+    let resp = observer.recv().await;
+    assert_eq!(resp["type"], "subscribed");
+    assert_eq!(resp["topics"], serde_json::json!(["render"]));
+
+    let resp = observer.recv().await;
+    assert_eq!(resp["type"], "render");
+    assert_eq!(resp["agents"][0]["session"], "proj#1");
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn render_observer_skips_auto_focus() {
+    let srv = TestServer::start("observer").await;
+    let mut observer = srv.connect().await;
+    let mut ext = srv.connect().await;
+    let mut agent = srv.connect().await;
+
+    observer.send(r#"{"type":"subscribe","topics":["render"]}"#).await;
+    assert_eq!(observer.recv().await["type"], "subscribed");
+    assert_eq!(observer.recv().await["type"], "render");
+
+    ext.send(r#"{"type":"auto_focus_config","enabled":true,"focus_delay_ms":0}"#).await;
+    ext.send(r#"{"type":"idle_status","idle":true}"#).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    agent.send(r#"{"type":"state","session":"proj#1","state":"awaiting","tool":"bash"}"#).await;
+
+    let resp = observer.recv().await;
+    assert_eq!(resp["type"], "render");
+
+    let mut saw_auto_focus = false;
+    while let Some(msg) = ext.recv_timeout(300).await {
+        if msg["type"] == "auto_focus" {
+            saw_auto_focus = true;
+        }
+    }
+    assert!(saw_auto_focus, "extension should receive auto_focus");
+
+    let extra = observer.recv_timeout(200).await;
+    assert!(extra.is_none(), "observer must not receive auto_focus, got {extra:?}");
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn unsubscribe_stops_broadcasts() {
+    let srv = TestServer::start("unsubscribe").await;
+    let mut observer = srv.connect().await;
+    let mut agent = srv.connect().await;
+
+    observer.send(r#"{"type":"subscribe","topics":["render","lifecycle"]}"#).await;
+    assert_eq!(observer.recv().await["type"], "subscribed");
+    assert_eq!(observer.recv().await["type"], "render");
+
+    observer.send(r#"{"type":"unsubscribe","topics":["render"]}"#).await;
+    let resp = observer.recv().await;
+    assert_eq!(resp["type"], "subscribed");
+    assert_eq!(resp["topics"], serde_json::json!(["lifecycle"]));
+
+    agent.send(r#"{"type":"state","session":"proj#1","state":"started","tool":"bash"}"#).await;
+    let resp = observer.recv().await;
+    assert_eq!(resp["type"], "lifecycle");
+    assert_eq!(resp["event"], "added");
+    assert_eq!(resp["session"], "proj#1");
+    assert_eq!(resp["state"], "started");
+
+    let extra = observer.recv_timeout(200).await;
+    assert!(extra.is_none(), "unsubscribed render must not arrive, got {extra:?}");
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn cli_click_forwarded_to_focus_subscriber() {
+    let srv = TestServer::start("focus_forward").await;
+    let mut ext = srv.connect().await;
+    let mut cli = srv.connect().await;
+
+    ext.send(r#"{"type":"subscribe","topics":["focus"]}"#).await;
+    assert_eq!(ext.recv().await["type"], "subscribed");
+
+    cli.send(r#"{"type":"click","session":"proj#1"}"#).await;
+    let resp = cli.recv().await;
+    assert_eq!(resp["type"], "focus");
+
+    let forwarded = ext.recv().await;
+    assert_eq!(forwarded["type"], "focus");
+    assert_eq!(forwarded["session"], "proj#1");
+
+    ext.send(r#"{"type":"click","session":"proj#2"}"#).await;
+    let resp = ext.recv().await;
+    assert_eq!(resp["session"], "proj#2");
+    let dup = ext.recv_timeout(200).await;
+    assert!(dup.is_none(), "focus subscriber should get its own click once, got {dup:?}");
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn render_delta_stream() {
+    let srv = TestServer::start("render_delta").await;
+    let mut observer = srv.connect().await;
+    let mut agent = srv.connect().await;
+
+    agent.send(r#"{"type":"state","session":"proj#1","state":"started","tool":"bash"}"#).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    observer.send(r#"{"type":"subscribe","topics":["render_delta"]}"#).await;
+    assert_eq!(observer.recv().await["type"], "subscribed");
+    let snapshot = observer.recv().await;
+    assert_eq!(snapshot["type"], "render");
+    let base_seq = snapshot["seq"].as_u64().unwrap();
+
+    agent.send(r#"{"type":"state","session":"proj#1","state":"awaiting","tool":"bash"}"#).await;
+    let delta = observer.recv().await;
+    assert_eq!(delta["type"], "render_delta");
+    assert_eq!(delta["base_seq"].as_u64().unwrap(), base_seq);
+    assert!(delta["seq"].as_u64().unwrap() > base_seq);
+    assert_eq!(delta["changed"][0]["session"], "proj#1");
+    assert_eq!(delta["changed"][0]["state"], "awaiting");
+    assert!(delta["order"].is_null());
+    let last_seq = delta["seq"].as_u64().unwrap();
+
+    agent.send(r#"{"type":"state","session":"proj#1","state":"awaiting","tool":"bash"}"#).await;
+    let none = observer.recv_timeout(200).await;
+    assert!(none.is_none(), "unchanged render should not produce a delta, got {none:?}");
+
+    agent.send(r#"{"type":"state","session":"proj#2","state":"working","tool":"bash"}"#).await;
+    let delta = observer.recv().await;
+    assert_eq!(delta["type"], "render_delta");
+    assert_eq!(delta["base_seq"].as_u64().unwrap(), last_seq);
+    assert_eq!(delta["added"][0]["session"], "proj#2");
+    assert_eq!(delta["order"], serde_json::json!(["proj#1", "proj#2"]));
+
+    observer.send(r#"{"type":"resync"}"#).await;
+    let full = observer.recv().await;
+    assert_eq!(full["type"], "render");
+    assert_eq!(full["seq"], delta["seq"]);
+    assert_eq!(full["agents"].as_array().unwrap().len(), 2);
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn render_delta_capability_picks_default_topic() {
+    let srv = TestServer::start("render_delta_capability").await;
+    let mut ext = srv.connect().await;
+    let mut agent = srv.connect().await;
+
+    ext.send(r#"{"type":"hello","protocol_version":1,"client":"extension","capabilities":["render_delta"]}"#).await;
+    assert_eq!(ext.recv().await["type"], "welcome");
+
+    agent.send(r#"{"type":"state","session":"proj#1","state":"started","tool":"bash"}"#).await;
+    let full = ext.recv().await;
+    assert_eq!(full["type"], "render");
+    assert_eq!(full["agents"][0]["session"], "proj#1");
+
+    agent.send(r#"{"type":"state","session":"proj#1","state":"awaiting","tool":"bash"}"#).await;
+    let delta = ext.recv().await;
+    assert_eq!(delta["type"], "render_delta");
+    assert_eq!(delta["base_seq"], full["seq"]);
+    assert_eq!(delta["changed"][0]["state"], "awaiting");
+
+    ext.send(r#"{"type":"subscribe","topics":[]}"#).await;
+    let subscribed = ext.recv().await;
+    assert_eq!(subscribed["type"], "subscribed");
+    let topics = subscribed["topics"].as_array().unwrap();
+    assert!(topics.contains(&serde_json::json!("render_delta")));
+    assert!(!topics.contains(&serde_json::json!("render")));
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn pipelined_requests_echo_ids() {
+    let srv = TestServer::start("request_ids").await;
+    let mut c = srv.connect().await;
+
+    c.send(r#"{"type":"state","session":"p#1","state":"awaiting","tool":"bash","id":1}"#).await;
+    c.send(r#"{"type":"click","session":"p#1","id":2}"#).await;
+    c.send(r#"{"type":"focus_next","id":"next"}"#).await;
+    c.send(r#"{"type":"bogus","id":4}"#).await;
+
+    let ack = c.recv().await;
+    assert_eq!(ack["type"], "ack");
+    assert_eq!(ack["id"], 1);
+
+    let click = c.recv().await;
+    assert_eq!(click["type"], "focus");
+    assert_eq!(click["id"], 2);
+    assert_eq!(click["session"], "p#1");
+
+    let next = c.recv().await;
+    assert_eq!(next["type"], "focus");
+    assert_eq!(next["id"], "next");
+
+    let err = c.recv().await;
+    assert_eq!(err["type"], "error");
+    assert_eq!(err["code"], "unsupported");
+    assert_eq!(err["id"], 4);
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn no_ack_without_id() {
+    let srv = TestServer::start("no_ack").await;
+    let mut c = srv.connect().await;
+
+    c.send(r#"{"type":"state","session":"p#1","state":"started","tool":"bash"}"#).await;
+    let resp = c.recv_timeout(200).await;
+    assert!(resp.is_none(), "no reply expected without id, got {resp:?}");
+
+    c.send(r#"{"type":"click","session":"p#1"}"#).await;
+    let resp = c.recv().await;
+    assert_eq!(resp["type"], "focus");
+    assert!(resp.get("id").is_none());
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn lagged_subscriber_gets_fresh_render() {
+    let srv = TestServer::start("lagged").await;
+    let mut slow = srv.connect().await;
+    let mut agent = srv.connect().await;
+
+    slow.send(r#"{"type":"subscribe","topics":["render"]}"#).await;
+    assert_eq!(slow.recv().await["type"], "subscribed");
+    assert_eq!(slow.recv().await["type"], "render");
+
+    let pad = "x".repeat(200);
+    for i in 0..400 {
+        let msg = format!(
+            r#"{{"type":"state","session":"{pad}{i}#1","state":"started","tool":"bash"}}"#
+        );
+        agent.send(&msg).await;
+    }
+    agent.send(r#"{"type":"state","session":"last#1","state":"awaiting","tool":"bash","id":"done"}"#).await;
+    assert_eq!(agent.recv().await["type"], "ack");
+
+    let mut last = None;
+    while let Some(msg) = slow.recv_timeout(500).await {
+        last = Some(msg);
+    }
+    let last = last.expect("slow client should still receive renders");
+    assert_eq!(last["type"], "render");
+    let agents = last["agents"].as_array().unwrap();
+    assert_eq!(agents.len(), 401, "last render must reflect the final state");
+    assert!(agents.iter().any(|a| a["session"] == "last#1" && a["state"] == "awaiting"));
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn batch_broadcasts_one_render() {
+    let srv = TestServer::start("batch").await;
+    let mut ext = srv.connect().await;
+    let mut agent = srv.connect().await;
+
+    ext.send(r#"{"type":"hello","protocol_version":1,"client":"extension"}"#).await;
+    assert_eq!(ext.recv().await["type"], "welcome");
+
+    agent
+        .send(concat!(
+            r#"{"type":"batch","id":"b1","messages":["#,
+            r#"{"type":"state","session":"a#1","state":"started","tool":"bash"},"#,
+            r#"{"type":"state","session":"b#1","state":"working","tool":"bash"},"#,
+            r#"{"type":"session_workspace","session":"b","workspace":1},"#,
+            r#"{"type":"list_sessions"}]}"#,
+        ))
+        .await;
+    let reply = agent.recv().await;
+    assert_eq!(reply["type"], "sessions");
+    assert_eq!(reply["id"], "b1");
+    assert_eq!(reply["sessions"].as_array().unwrap().len(), 2);
+
+    let render = ext.recv().await;
+    assert_eq!(render["type"], "render");
+    assert_eq!(render["agents"].as_array().unwrap().len(), 2);
+    assert!(ext.recv_timeout(200).await.is_none(), "expected a single render for the batch");
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn lifecycle_not_broadcast_without_subscribers() {
+    let config = Config { channel_capacity: 4, ..Config::default() };
+    let srv = TestServer::start_with("lifecycle_idle", |p| SocketServer::with_config(p, config)).await;
+    let mut ext = srv.connect().await;
+    let mut agent = srv.connect().await;
+
+    ext.send(r#"{"type":"hello","protocol_version":1,"client":"extension"}"#).await;
+    assert_eq!(ext.recv().await["type"], "welcome");
+
+    let batch = |prefix: &str| {
+        let messages: Vec<String> = (0..8)
+            .map(|i| format!(r#"{{"type":"state","session":"{prefix}{i}#1","state":"started","tool":"bash"}}"#))
+            .collect();
+        format!(r#"{{"type":"batch","messages":[{}]}}"#, messages.join(","))
+    };
+
+    agent.send(&batch("a")).await;
+    let render = ext.recv().await;
+    assert_eq!(render["agents"].as_array().unwrap().len(), 8);
+    assert!(ext.recv_timeout(200).await.is_none(), "lifecycle events must not make the extension lag");
+
+    let mut watcher = srv.connect().await;
+    watcher.send(r#"{"type":"subscribe","topics":["lifecycle"]}"#).await;
+    assert_eq!(watcher.recv().await["type"], "subscribed");
+    agent.send(r#"{"type":"state","session":"w#1","state":"started","tool":"bash"}"#).await;
+    assert_eq!(watcher.recv().await["type"], "lifecycle");
+    assert_eq!(ext.recv().await["type"], "render");
+    drop(watcher);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    agent.send(&batch("b")).await;
+    let render = ext.recv().await;
+    assert_eq!(render["agents"].as_array().unwrap().len(), 17);
+    assert!(ext.recv_timeout(200).await.is_none(), "a departed lifecycle subscriber must not be counted");
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn restores_state_file_on_startup() {
+    let dir = std::env::temp_dir().join("argus-test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let state_file = dir.join(format!("restore-{}.json", std::process::id()));
+
+    let mut before = StateManager::new();
+    before.update_state("p#1".into(), AgentState::Awaiting, "Bash".into(), "claude".into());
+    before.update_state("q#1".into(), AgentState::Ended, String::new(), "claude".into());
+    before.update_workspace("p", 3, 1);
+    persist::save(&state_file, &before.to_snapshot()).await.unwrap();
+
+    let srv = TestServer::start_with("restore", |p| SocketServer::new(p).with_state_file(state_file.clone())).await;
+    let mut cli = srv.connect().await;
+    cli.send(r#"{"type":"list_sessions"}"#).await;
+    let resp = cli.recv().await;
+    let sessions = resp["sessions"].as_array().unwrap();
+    assert_eq!(sessions.len(), 1, "ended session is not restored: {resp}");
+    assert_eq!(sessions[0]["session"], "p#1");
+    assert_eq!(sessions[0]["state"], "awaiting");
+    assert_eq!(sessions[0]["tool"], "Bash");
+
+    srv.shutdown().await;
+    let _ = std::fs::remove_file(&state_file);
+}
+
 #[tokio::test]
 async fn focus_next_returns_session() {
     let srv = TestServer::start("focus_next").await;
@@ -185,6 +627,20 @@ async fn invalid_json_survives() {
     c.send(r#"{"broken json"#).await;
 
     c.send(r#"{"type":"click","session":"ok#1"}"#).await;
+
+    let err = c.recv().await;
+    assert_eq!(err["type"], "error");
+    assert_eq!(err["code"], "invalid_json");
+
+    let err = c.recv().await;
+    assert_eq!(err["type"], "error");
+    assert_eq!(err["code"], "unsupported");
+    assert_eq!(err["message_type"], "totally_unknown");
+
+    let err = c.recv().await;
+    assert_eq!(err["type"], "error");
+    assert_eq!(err["code"], "invalid_json");
+
     let resp = c.recv().await;
 
     assert_eq!(resp["type"], "focus");
@@ -202,6 +658,11 @@ async fn oversize_line_dropped() {
     c.send(&huge).await;
 
     c.send(r#"{"type":"click","session":"after_huge#1"}"#).await;
+
+    let err = c.recv().await;
+    assert_eq!(err["type"], "error");
+    assert_eq!(err["code"], "line_too_long");
+
     let resp = c.recv().await;
 
     assert_eq!(resp["type"], "focus");
@@ -210,6 +671,161 @@ async fn oversize_line_dropped() {
     srv.shutdown().await;
 }
 
+#[tokio::test]
+async fn invalid_message_reports_type() {
+    let srv = TestServer::start("invalid_message").await;
+    let mut c = srv.connect().await;
+
+    c.send(r#"{"type":"click"}"#).await;
+    let err = c.recv().await;
+
+    assert_eq!(err["type"], "error");
+    assert_eq!(err["code"], "invalid_message");
+    assert_eq!(err["message_type"], "click");
+    assert!(err["message"].as_str().unwrap().contains("session"));
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn auto_focus_dwell_holds_next_switch() {
+    let config = Config { auto_focus_dwell_secs: 1, ..Config::default() };
+    let srv = TestServer::start_with("dwell", |p| SocketServer::with_config(p, config)).await;
+    let mut ext = srv.connect().await;
+    let mut agent = srv.connect().await;
+
+    ext.send(r#"{"type":"auto_focus_config","enabled":true,"focus_delay_ms":0}"#).await;
+    ext.send(r#"{"type":"idle_status","idle":true}"#).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    agent.send(r#"{"type":"state","session":"a#1","state":"awaiting","tool":"bash"}"#).await;
+    agent.send(r#"{"type":"state","session":"b#1","state":"awaiting","tool":"bash"}"#).await;
+
+    let auto_focus = |msg: &serde_json::Value| (msg["type"] == "auto_focus").then(|| msg["session"].clone());
+    let mut first = None;
+    while first.is_none() {
+        first = auto_focus(&ext.recv().await);
+    }
+    assert_eq!(first.unwrap(), "a#1");
+
+    agent.send(r#"{"type":"state","session":"a#1","state":"working","tool":"bash"}"#).await;
+    while let Some(msg) = ext.recv_timeout(500).await {
+        assert_eq!(auto_focus(&msg), None, "switched during dwell: {msg}");
+    }
+    let mut next = None;
+    while next.is_none() {
+        next = auto_focus(&ext.recv().await);
+    }
+    assert_eq!(next.unwrap(), "b#1");
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn return_destination_survives_reconnect() {
+    let srv = TestServer::start("returnto").await;
+    let mut ext = srv.connect().await;
+    let mut agent = srv.connect().await;
+
+    ext.send(r#"{"type":"hello","protocol_version":1,"client":"extension"}"#).await;
+    ext.send(r#"{"type":"location","workspace":2,"monitor":0,"window":"77"}"#).await;
+    ext.send(r#"{"type":"auto_focus_config","enabled":true,"focus_delay_ms":0}"#).await;
+    ext.send(r#"{"type":"idle_status","idle":true}"#).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    agent.send(r#"{"type":"state","session":"a#1","state":"awaiting","tool":"bash"}"#).await;
+    while ext.recv().await["type"] != "auto_focus" {}
+    drop(ext);
+
+    let mut ext = srv.connect().await;
+    ext.send(r#"{"type":"hello","protocol_version":1,"client":"extension"}"#).await;
+    assert_eq!(ext.recv().await["type"], "welcome");
+    agent.send(r#"{"type":"state","session":"a#1","state":"working","tool":"bash"}"#).await;
+    let mut msg = ext.recv().await;
+    while msg["type"] != "return_workspace" {
+        msg = ext.recv().await;
+    }
+    assert_eq!(msg["destination"], serde_json::json!({"workspace": 2, "monitor": 0, "window": "77"}));
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn dnd_summary_replaces_lifecycle() {
+    let srv = TestServer::start("dnd").await;
+    let mut observer = srv.connect().await;
+    let mut cli = srv.connect().await;
+
+    observer.send(r#"{"type":"subscribe","topics":["lifecycle","dnd"]}"#).await;
+    assert_eq!(observer.recv().await["type"], "subscribed");
+
+    cli.send(r#"{"type":"dnd","enabled":true,"duration_secs":2700}"#).await;
+    let msg = observer.recv().await;
+    assert_eq!(msg["type"], "dnd_started");
+    assert_eq!(msg["reason"], "manual");
+    assert!(msg["until"].as_u64().is_some());
+
+    cli.send(r#"{"type":"state","session":"proj#1","state":"awaiting","tool":"bash"}"#).await;
+    assert!(observer.recv_timeout(200).await.is_none(), "lifecycle held back");
+
+    cli.send(r#"{"type":"dnd","enabled":false}"#).await;
+    let msg = observer.recv().await;
+    assert_eq!(msg["type"], "dnd_ended");
+    assert_eq!(
+        msg["changes"],
+        serde_json::json!([{"session": "proj#1", "previous": null, "state": "awaiting"}])
+    );
+    assert_eq!(msg["agents"][0]["session"], "proj#1");
+
+    cli.send(r#"{"type":"state","session":"proj#1","state":"working","tool":"bash"}"#).await;
+    assert_eq!(observer.recv().await["type"], "lifecycle");
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn rejected_state_is_not_acked() {
+    let srv = TestServer::start("rejected").await;
+    let mut hook = srv.connect().await;
+
+    hook.send(r#"{"id":1,"type":"state","session":"p#1","state":"working","tool":"bash","seq":30}"#).await;
+    assert_eq!(hook.recv().await, serde_json::json!({"type": "ack", "id": 1}));
+
+    hook.send(r#"{"id":2,"type":"state","session":"p#1","state":"awaiting","tool":"bash","seq":20}"#).await;
+    let err = hook.recv().await;
+    assert_eq!(err["type"], "error");
+    assert_eq!(err["id"], 2);
+    assert_eq!(err["code"], "rejected");
+    assert_eq!(err["message_type"], "state");
+    assert!(err["message"].as_str().unwrap().starts_with("Stale"), "{err}");
+
+    hook.send(r#"{"id":3,"type":"state","session":"p#1","state":"ended","tool":""}"#).await;
+    assert_eq!(hook.recv().await["type"], "ack");
+    hook.send(r#"{"id":4,"type":"state","session":"p#1","state":"awaiting","tool":"bash"}"#).await;
+    let err = hook.recv().await;
+    assert_eq!(err["code"], "rejected");
+    assert!(err["message"].as_str().unwrap().starts_with("Illegal"), "{err}");
+
+    hook.send(r#"{"type":"state","session":"p#2","state":"working","tool":"bash","seq":30}"#).await;
+    hook.send(r#"{"type":"state","session":"p#2","state":"awaiting","tool":"bash","seq":20}"#).await;
+    let none = hook.recv_timeout(200).await;
+    assert!(none.is_none(), "a rejected update without id must stay silent, got {none:?}");
+
+    srv.shutdown().await;
+}
+
+#[tokio::test]
+async fn max_line_length_from_config() {
+    let config = Config { max_line_length: 1024, ..Config::default() };
+    let srv = TestServer::start_with("maxline", |p| SocketServer::with_config(p, config)).await;
+    let mut client = srv.connect().await;
+
+    client.send(&format!(r#"{{"type":"click","session":"{}"}}"#, "x".repeat(2000))).await;
+    let resp = client.recv().await;
+    assert_eq!(resp["code"], "line_too_long");
+    assert_eq!(resp["message"], "line exceeds 1024 bytes");
+
+    srv.shutdown().await;
+}
+
 #[tokio::test]
 async fn empty_lines_ignored() {
     let srv = TestServer::start("empty_lines").await;
@@ -1,10 +1,58 @@
 mod common;
 
 use argus_agenticus::handler;
-use argus_agenticus::protocol::{AgentState, IncomingMessage};
+use argus_agenticus::protocol::{
+    AgentState, ClientKind, DndChange, DndReason, ErrorCode, IncomingMessage, OutgoingMessage, Topic, MIN_PROTOCOL_VERSION,
+    PROTOCOL_VERSION,
+};
+
+use argus_agenticus::state::DndEvent;
 
 use common::*;
 
+#[tokio::test]
+async fn hello_replies_welcome() {
+    let state = fresh_state();
+    let fx = handler::process(msg_hello(PROTOCOL_VERSION, ClientKind::Cli), &state).await;
+    should_reply_welcome(&fx);
+    should_not_broadcast(&fx);
+    should_not_mark_extension(&fx);
+    assert!(!fx.disconnect);
+    let client = fx.client.expect("client info recorded");
+    assert_eq!(client.kind, ClientKind::Cli);
+    assert_eq!(client.protocol_version, PROTOCOL_VERSION);
+}
+
+#[tokio::test]
+async fn hello_extension_marks_extension() {
+    let state = fresh_state();
+    let fx = handler::process(msg_hello(PROTOCOL_VERSION, ClientKind::Extension), &state).await;
+    should_reply_welcome(&fx);
+    should_mark_extension(&fx);
+}
+
+#[tokio::test]
+async fn hello_newer_client_accepted() {
+    let state = fresh_state();
+    let fx = handler::process(msg_hello(PROTOCOL_VERSION + 1, ClientKind::Dashboard), &state).await;
+    should_reply_welcome(&fx);
+    assert!(!fx.disconnect);
+}
+
+#[tokio::test]
+async fn hello_outdated_client_refused() {
+    let state = fresh_state();
+    let fx = handler::process(msg_hello(MIN_PROTOCOL_VERSION - 1, ClientKind::Extension), &state).await;
+    assert!(
+        matches!(fx.replies.as_slice(), [OutgoingMessage::Refused { .. }]),
+        "expected Refused reply, got {:?}",
+        fx.replies
+    );
+    assert!(fx.disconnect);
+    assert!(fx.client.is_none());
+    should_not_mark_extension(&fx);
+}
+
 #[tokio::test]
 async fn state_started_broadcasts() {
     let state = fresh_state();
@@ -91,6 +139,30 @@ async fn focus_next_with_sessions() {
     should_not_broadcast(&fx);
 }
 
+#[tokio::test]
+async fn navigation_commands_reply_focus() {
+    let state = fresh_state();
+    handler::process(msg_state("p#1", AgentState::Started), &state).await;
+    handler::process(msg_state("q#1", AgentState::Awaiting), &state).await;
+    handler::process(msg_window_focus("q"), &state).await;
+    handler::process(msg_window_focus("p"), &state).await;
+
+    let fx = handler::process(IncomingMessage::FocusPrev, &state).await;
+    should_reply_focus(&fx, "q#1");
+    let fx = handler::process(IncomingMessage::FocusNextInState { state: AgentState::Started }, &state).await;
+    should_reply_focus(&fx, "p#1");
+    let fx = handler::process(IncomingMessage::FocusIndex { n: 2 }, &state).await;
+    should_reply_focus(&fx, "q#1");
+    let fx = handler::process(IncomingMessage::FocusGroup { group: to_s("p") }, &state).await;
+    should_reply_focus(&fx, "p#1");
+    let fx = handler::process(IncomingMessage::FocusLast, &state).await;
+    should_reply_focus(&fx, "q#1");
+
+    let fx = handler::process(IncomingMessage::FocusIndex { n: 9 }, &state).await;
+    should_have_no_reply(&fx);
+    should_not_broadcast(&fx);
+}
+
 #[tokio::test]
 async fn focus_next_empty() {
     let state = fresh_state();
@@ -190,6 +262,531 @@ async fn window_closed_unknown_session() {
     should_have_no_reply(&fx);
 }
 
+#[tokio::test]
+async fn list_sessions_replies_snapshot() {
+    let state = fresh_state();
+    handler::process(msg_state("p#1", AgentState::Started), &state).await;
+    handler::process(msg_state("p#2", AgentState::Awaiting), &state).await;
+
+    let fx = handler::process(msg_list_sessions(None), &state).await;
+    should_not_broadcast(&fx);
+    should_not_mark_extension(&fx);
+    should_no_auto_focus(&fx);
+    match fx.replies.as_slice() {
+        [OutgoingMessage::Sessions { sessions }] => {
+            assert_eq!(sessions.len(), 2);
+            assert_eq!(sessions[0].agent.session, "p#1");
+            assert_eq!(sessions[0].tool, "bash");
+            assert!(sessions[0].last_activity_at > 0);
+            assert!(sessions[0].ended_at.is_none());
+        }
+        other => panic!("expected Sessions reply, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn list_sessions_filters_by_state() {
+    let state = fresh_state();
+    handler::process(msg_state("p#1", AgentState::Started), &state).await;
+    handler::process(msg_state("p#2", AgentState::Awaiting), &state).await;
+
+    let fx = handler::process(msg_list_sessions(Some(AgentState::Awaiting)), &state).await;
+    match fx.replies.as_slice() {
+        [OutgoingMessage::Sessions { sessions }] => {
+            assert_eq!(sessions.len(), 1);
+            assert_eq!(sessions[0].agent.session, "p#2");
+        }
+        other => panic!("expected Sessions reply, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn get_session_found() {
+    let state = fresh_state();
+    handler::process(msg_state("p#1", AgentState::Ended), &state).await;
+
+    let fx = handler::process(msg_get_session("p#1"), &state).await;
+    should_not_broadcast(&fx);
+    should_not_mark_extension(&fx);
+    match fx.replies.as_slice() {
+        [OutgoingMessage::Session { session, snapshot: Some(snap) }] => {
+            assert_eq!(session, "p#1");
+            assert_eq!(snap.agent.state, AgentState::Ended);
+            assert!(snap.ended_at.is_some());
+        }
+        other => panic!("expected Session reply with snapshot, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn get_session_unknown() {
+    let state = fresh_state();
+    let fx = handler::process(msg_get_session("nope#1"), &state).await;
+    match fx.replies.as_slice() {
+        [OutgoingMessage::Session { session, snapshot: None }] => assert_eq!(session, "nope#1"),
+        other => panic!("expected empty Session reply, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn subscribe_does_not_mark_extension() {
+    let state = fresh_state();
+    let fx = handler::process(msg_subscribe(&[Topic::Render, Topic::Lifecycle]), &state).await;
+    assert_eq!(fx.subscribe, Some(vec![Topic::Render, Topic::Lifecycle]));
+    assert!(fx.unsubscribe.is_none());
+    should_not_mark_extension(&fx);
+    should_not_broadcast(&fx);
+    should_have_no_reply(&fx);
+}
+
+#[tokio::test]
+async fn unsubscribe_effects() {
+    let state = fresh_state();
+    let fx = handler::process(IncomingMessage::Unsubscribe { topics: vec![Topic::AutoFocus] }, &state).await;
+    assert_eq!(fx.unsubscribe, Some(vec![Topic::AutoFocus]));
+    assert!(fx.subscribe.is_none());
+    should_no_auto_focus(&fx);
+}
+
+#[tokio::test]
+async fn resync_replies_full_render() {
+    let state = fresh_state();
+    handler::process(msg_state("p#1", AgentState::Started), &state).await;
+    state.lock().await.next_render_seq();
+
+    let fx = handler::process(IncomingMessage::Resync, &state).await;
+    should_not_broadcast(&fx);
+    should_not_mark_extension(&fx);
+    match fx.replies.as_slice() {
+        [OutgoingMessage::Render { seq, agents }] => {
+            assert_eq!(*seq, 1);
+            assert_eq!(agents.len(), 1);
+            assert_eq!(agents[0].session, "p#1");
+        }
+        other => panic!("expected Render reply, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn state_metadata_merges_across_updates() {
+    let state = fresh_state();
+    let first = serde_json::json!({
+        "type": "state", "session": "p#1", "state": "started", "tool": "",
+        "cwd": "/home/u/p", "git_branch": "main", "pid": 100, "model": "opus",
+    });
+    let second = serde_json::json!({
+        "type": "state", "session": "p#1", "state": "working", "tool": "Bash", "git_branch": "fix",
+    });
+    for msg in [first, second] {
+        handler::process(serde_json::from_value(msg).unwrap(), &state).await;
+    }
+
+    let s = state.lock().await;
+    let agent = &s.get_render_data()[0];
+    assert_eq!(agent.state, AgentState::Working);
+    assert_eq!(agent.meta.cwd.as_deref(), Some("/home/u/p"));
+    assert_eq!(agent.meta.git_branch.as_deref(), Some("fix"));
+    assert_eq!(agent.meta.pid, Some(100));
+    assert_eq!(agent.meta.model.as_deref(), Some("opus"));
+    assert!(agent.meta.terminal.is_none());
+}
+
+#[tokio::test]
+async fn state_since_moves_only_on_transition() {
+    let state = fresh_state();
+
+    handler::process(msg_state("p#1", AgentState::Awaiting), &state).await;
+    let entered = state.lock().await.get_render_data()[0].state_since;
+    assert!(entered > 0);
+
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    handler::process(msg_state("p#1", AgentState::Awaiting), &state).await;
+    let since = state.lock().await.get_render_data()[0].state_since;
+    assert_eq!(since, entered, "same state keeps state_since");
+
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    handler::process(msg_state("p#1", AgentState::Working), &state).await;
+    let since = state.lock().await.get_render_data()[0].state_since;
+    assert!(since > entered, "transition resets state_since");
+}
+
+#[tokio::test]
+async fn session_history_records_updates() {
+    let state = fresh_state();
+    for (st, tool, event) in [
+        ("started", "", "SessionStart"),
+        ("working", "Bash", "PreToolUse"),
+        ("working", "Bash", "PostToolUse"),
+        ("completed", "", "Stop"),
+    ] {
+        let msg = serde_json::json!({
+            "type": "state", "session": "p#1", "state": st, "tool": tool, "event": event,
+        });
+        handler::process(serde_json::from_value(msg).unwrap(), &state).await;
+    }
+    handler::process(msg_window_focus("p - editor"), &state).await;
+
+    let fx = handler::process(IncomingMessage::SessionHistory { session: to_s("p#1") }, &state).await;
+    should_not_broadcast(&fx);
+    let transitions = match fx.replies.as_slice() {
+        [OutgoingMessage::History { session, transitions: Some(t) }] => {
+            assert_eq!(session, "p#1");
+            t.clone()
+        }
+        other => panic!("expected History reply, got {other:?}"),
+    };
+    let trail: Vec<_> = transitions
+        .iter()
+        .map(|t| (t.previous, t.state, t.event.as_deref().unwrap_or("")))
+        .collect();
+    assert_eq!(
+        trail,
+        vec![
+            (None, AgentState::Started, "SessionStart"),
+            (Some(AgentState::Started), AgentState::Working, "PreToolUse"),
+            (Some(AgentState::Working), AgentState::Working, "PostToolUse"),
+            (Some(AgentState::Working), AgentState::Completed, "Stop"),
+            (Some(AgentState::Completed), AgentState::Started, "window_focus"),
+        ]
+    );
+    assert_eq!(transitions[1].tool, "Bash");
+    assert!(transitions.windows(2).all(|w| w[0].at <= w[1].at));
+}
+
+#[tokio::test]
+async fn session_history_is_bounded() {
+    let state = fresh_state();
+    for _ in 0..50 {
+        handler::process(msg_state("p#1", AgentState::Working), &state).await;
+    }
+    let history = state.lock().await.get_history("p#1").unwrap();
+    assert_eq!(history.len(), 32);
+    assert_eq!(history[0].previous, Some(AgentState::Working));
+}
+
+#[tokio::test]
+async fn session_history_unknown_session() {
+    let state = fresh_state();
+    let fx = handler::process(IncomingMessage::SessionHistory { session: to_s("nope#1") }, &state).await;
+    match fx.replies.as_slice() {
+        [OutgoingMessage::History { transitions: None, .. }] => {}
+        other => panic!("expected empty History reply, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn batch_merges_reconnect_burst() {
+    let state = fresh_state();
+    handler::process(msg_state("a#1", AgentState::Started), &state).await;
+    handler::process(msg_state("b#1", AgentState::Started), &state).await;
+
+    let fx = handler::process(
+        msg_batch(vec![
+            msg_window_focus("a#1"),
+            msg_workspace("a", 1),
+            msg_workspace("b", 2),
+            msg_auto_focus_config(true, 500),
+        ]),
+        &state,
+    )
+    .await;
+    should_broadcast(&fx);
+    should_mark_extension(&fx);
+    should_trigger(&fx);
+    should_have_no_reply(&fx);
+
+    let s = state.lock().await;
+    let render = s.get_render_data();
+    assert!(render.iter().find(|a| a.session == "a#1").unwrap().focused);
+}
+
+#[tokio::test]
+async fn batch_collects_replies_in_order() {
+    let state = fresh_state();
+    let fx = handler::process(
+        msg_batch(vec![
+            msg_state("p#1", AgentState::Awaiting),
+            msg_get_session("p#1"),
+            msg_list_sessions(None),
+        ]),
+        &state,
+    )
+    .await;
+    should_broadcast(&fx);
+    match fx.replies.as_slice() {
+        [OutgoingMessage::Session { snapshot: Some(_), .. }, OutgoingMessage::Sessions { sessions }] => {
+            assert_eq!(sessions.len(), 1);
+        }
+        other => panic!("expected Session then Sessions, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn batch_keeps_last_auto_focus_event() {
+    let state = fresh_state();
+    handler::process(msg_state("p#1", AgentState::Awaiting), &state).await;
+    state.lock().await.next_awaiting();
+    let fx = handler::process(
+        msg_batch(vec![msg_idle(true), msg_state("p#1", AgentState::Working), msg_list_sessions(None)]),
+        &state,
+    )
+    .await;
+    should_queue_empty(&fx);
+}
+
+#[tokio::test]
+async fn batch_stops_after_refused_hello() {
+    let state = fresh_state();
+    let fx = handler::process(
+        msg_batch(vec![
+            msg_hello(MIN_PROTOCOL_VERSION - 1, ClientKind::Extension),
+            msg_state("p#1", AgentState::Started),
+        ]),
+        &state,
+    )
+    .await;
+    assert!(fx.disconnect);
+    should_not_broadcast(&fx);
+    assert_eq!(fx.replies.len(), 1);
+    assert!(state.lock().await.get_render_data().is_empty());
+}
+
+#[tokio::test]
+async fn batch_subscription_changes_apply_in_order() {
+    let state = fresh_state();
+    let fx = handler::process(
+        msg_batch(vec![
+            msg_subscribe(&[Topic::Render, Topic::Lifecycle]),
+            IncomingMessage::Unsubscribe { topics: vec![Topic::Render] },
+            msg_subscribe(&[Topic::Focus]),
+        ]),
+        &state,
+    )
+    .await;
+    assert_eq!(fx.subscribe, Some(vec![Topic::Lifecycle, Topic::Focus]));
+    assert_eq!(fx.unsubscribe, Some(vec![Topic::Render]));
+}
+
+#[tokio::test]
+async fn out_of_order_state_is_dropped() {
+    let state = fresh_state();
+    handler::process(msg_state_seq("p#1", AgentState::Working, 10), &state).await;
+    handler::process(msg_state_seq("p#1", AgentState::Working, 30), &state).await;
+
+    let fx = handler::process(msg_state_seq("p#1", AgentState::Awaiting, 20), &state).await;
+    should_not_broadcast(&fx);
+    should_no_auto_focus(&fx);
+    assert!(
+        matches!(&fx.rejections[..], [OutgoingMessage::Error { code: ErrorCode::Rejected, message, .. }] if message.starts_with("Stale")),
+        "got {:?}",
+        fx.rejections
+    );
+    should_have_no_reply(&fx);
+    assert_eq!(state.lock().await.get_render_data()[0].state, AgentState::Working);
+
+    handler::process(msg_state("p#1", AgentState::Awaiting), &state).await;
+    assert_eq!(state.lock().await.get_render_data()[0].state, AgentState::Awaiting, "no seq is always applied");
+    handler::process(msg_state_seq("p#1", AgentState::Working, 30), &state).await;
+    assert_eq!(state.lock().await.get_render_data()[0].state, AgentState::Working, "equal seq is applied");
+}
+
+#[tokio::test]
+async fn ended_session_ignores_stragglers() {
+    let state = fresh_state();
+    handler::process(msg_state("p#1", AgentState::Working), &state).await;
+    handler::process(msg_state("p#1", AgentState::Ended), &state).await;
+
+    for late in [AgentState::Working, AgentState::Awaiting, AgentState::Completed, AgentState::Ended] {
+        let fx = handler::process(msg_state("p#1", late), &state).await;
+        should_not_broadcast(&fx);
+    }
+    assert_eq!(state.lock().await.get_render_data()[0].state, AgentState::Ended);
+
+    let fx = handler::process(msg_state("p#1", AgentState::Started), &state).await;
+    should_broadcast(&fx);
+    assert_eq!(state.lock().await.get_render_data()[0].state, AgentState::Started);
+}
+
+#[tokio::test]
+async fn subagent_aggregates_into_parent() {
+    let state = fresh_state();
+    handler::process(msg_state("p#1", AgentState::Working), &state).await;
+    handler::process(msg_state("q#1", AgentState::Started), &state).await;
+    handler::process(msg_subagent_state("p#1-task", "p#1", AgentState::Awaiting), &state).await;
+
+    let data = state.lock().await.get_render_data();
+    let sessions: Vec<&str> = data.iter().map(|a| a.session.as_str()).collect();
+    assert_eq!(sessions, ["p#1", "p#1-task", "q#1"], "child sorts right after its parent");
+    assert_eq!(data[0].aggregate_state, Some(AgentState::Awaiting));
+    assert_eq!(data[1].parent_session.as_deref(), Some("p#1"));
+    assert_eq!(data[1].group, data[0].group);
+    assert_eq!(data[1].aggregate_state, None);
+    assert_eq!(data[2].aggregate_state, None);
+
+    handler::process(msg_state("p#1-task", AgentState::Completed), &state).await;
+    let data = state.lock().await.get_render_data();
+    assert_eq!(data[0].aggregate_state, Some(AgentState::Working), "parent keeps its child link");
+}
+
+#[tokio::test]
+async fn subagent_focus_goes_to_parent() {
+    let state = fresh_state();
+    handler::process(msg_state("p#1", AgentState::Working), &state).await;
+    handler::process(msg_subagent_state("p#1-a", "p#1", AgentState::Working), &state).await;
+    handler::process(msg_subagent_state("p#1-b", "p#1-a", AgentState::Awaiting), &state).await;
+
+    let fx = handler::process(msg_click("p#1-b"), &state).await;
+    should_reply_focus(&fx, "p#1");
+    assert_eq!(state.lock().await.next_awaiting().as_deref(), Some("p#1"));
+}
+
+#[tokio::test]
+async fn subagent_parent_cycle_is_ignored() {
+    let state = fresh_state();
+    handler::process(msg_state("a#1", AgentState::Working), &state).await;
+    handler::process(msg_subagent_state("b#1", "a#1", AgentState::Working), &state).await;
+    handler::process(msg_subagent_state("a#1", "b#1", AgentState::Working), &state).await;
+    handler::process(msg_subagent_state("a#1", "a#1", AgentState::Working), &state).await;
+
+    let data = state.lock().await.get_render_data();
+    assert_eq!(data[0].session, "a#1");
+    assert_eq!(data[0].parent_session, None);
+    assert_eq!(data[1].parent_session.as_deref(), Some("a#1"));
+}
+
+#[tokio::test]
+async fn explicit_group_overrides_session_id() {
+    let state = fresh_state();
+    handler::process(msg_grouped_state("api#1", "work", Some("Work")), &state).await;
+    handler::process(msg_grouped_state("web#3", "work", None), &state).await;
+    handler::process(msg_state("notes#1", AgentState::Working), &state).await;
+
+    let data = state.lock().await.get_render_data();
+    let groups: Vec<(&str, u32, &str)> = data.iter().map(|a| (a.session.as_str(), a.group, a.group_key.as_str())).collect();
+    assert_eq!(groups, [("notes#1", 0, "notes"), ("api#1", 1, "work"), ("web#3", 1, "work")]);
+    assert_eq!(data[1].group_label.as_deref(), Some("Work"));
+    assert_eq!(data[2].group_label.as_deref(), Some("Work"), "one session's label names the group");
+
+    handler::process(msg_state("api#1", AgentState::Completed), &state).await;
+    handler::process(msg_window_focus("web"), &state).await;
+    let data = state.lock().await.get_render_data();
+    assert!(data[1].focused && data[2].focused, "focusing one window focuses its whole group");
+    assert_eq!(data[1].state, AgentState::Started, "completed demoted across the group");
+}
+
+#[tokio::test]
+async fn return_destination_is_location_at_run_start() {
+    let state = fresh_state();
+    let fx = handler::process(msg_location(1, "editor"), &state).await;
+    should_have_no_reply(&fx);
+    should_not_broadcast(&fx);
+    handler::process(msg_state("p#1", AgentState::Awaiting), &state).await;
+    state.lock().await.next_awaiting();
+    handler::process(msg_location(4, "terminal"), &state).await;
+    handler::process(msg_window_focus("p#1"), &state).await;
+
+    let fx = handler::process(msg_state("p#1", AgentState::Working), &state).await;
+    should_queue_empty(&fx);
+    let mut s = state.lock().await;
+    let destination = s.take_return_destination().expect("destination recorded");
+    assert_eq!((destination.workspace, destination.window.as_deref()), (1, Some("editor")));
+    assert!(s.take_return_destination().is_none(), "taken once per run");
+}
+
+#[tokio::test]
+async fn manual_switch_cancels_return_destination() {
+    let state = fresh_state();
+    handler::process(msg_location(1, "editor"), &state).await;
+    handler::process(msg_state("p#1", AgentState::Awaiting), &state).await;
+    state.lock().await.next_awaiting();
+    handler::process(msg_window_focus("p#1"), &state).await;
+    handler::process(msg_window_focus("Firefox"), &state).await;
+
+    let fx = handler::process(msg_state("p#1", AgentState::Working), &state).await;
+    should_queue_empty(&fx);
+    assert!(state.lock().await.take_return_destination().is_none());
+}
+
+#[tokio::test]
+async fn run_ended_outside_state_updates_records_fresh_destination() {
+    let ends: [fn() -> IncomingMessage; 3] = [
+        || IncomingMessage::MarkAllStarted,
+        || IncomingMessage::ClearAgents,
+        || msg_window_closed("p#1"),
+    ];
+    for end in ends {
+        let state = fresh_state();
+        handler::process(msg_location(1, "editor"), &state).await;
+        handler::process(msg_state("p#1", AgentState::Awaiting), &state).await;
+        state.lock().await.next_awaiting();
+
+        let fx = handler::process(end(), &state).await;
+        should_queue_empty(&fx);
+        let destination = state.lock().await.take_return_destination().expect("destination recorded");
+        assert_eq!(destination.workspace, 1);
+
+        handler::process(msg_location(5, "browser"), &state).await;
+        handler::process(msg_state("q#1", AgentState::Awaiting), &state).await;
+        state.lock().await.next_awaiting();
+        let fx = handler::process(msg_state("q#1", AgentState::Working), &state).await;
+        should_queue_empty(&fx);
+        let destination = state.lock().await.take_return_destination().expect("new run records its origin");
+        assert_eq!(destination.workspace, 5);
+    }
+}
+
+#[tokio::test]
+async fn dnd_holds_back_and_summarizes() {
+    let state = fresh_state();
+    handler::process(msg_state("p#1", AgentState::Started), &state).await;
+    handler::process(msg_state("q#1", AgentState::Started), &state).await;
+    {
+        let mut s = state.lock().await;
+        s.take_changes();
+        s.set_idle(true);
+        s.set_auto_focus_config(true, 0);
+    }
+
+    handler::process(IncomingMessage::Dnd { enabled: true, duration_secs: None }, &state).await;
+    handler::process(msg_state("p#1", AgentState::Awaiting), &state).await;
+    handler::process(msg_state("q#1", AgentState::Working), &state).await;
+    handler::process(msg_state("q#1", AgentState::Started), &state).await;
+    handler::process(msg_state("r#1", AgentState::Started), &state).await;
+    {
+        let mut s = state.lock().await;
+        assert!(!s.should_auto_focus());
+        assert!(s.take_changes().is_empty(), "lifecycle held back");
+        assert_eq!(s.take_dnd_events(), vec![DndEvent::Started { reason: DndReason::Manual, until: None }]);
+    }
+
+    let fx = handler::process(IncomingMessage::Dnd { enabled: false, duration_secs: None }, &state).await;
+    should_trigger(&fx);
+    let mut s = state.lock().await;
+    assert!(s.should_auto_focus());
+    let events = s.take_dnd_events();
+    let [DndEvent::Ended { changes, .. }] = &events[..] else {
+        panic!("expected one Ended, got {events:?}");
+    };
+    let change = |session: &str, previous, state| DndChange { session: to_s(session), previous, state };
+    assert_eq!(
+        changes,
+        &vec![
+            change("p#1", Some(AgentState::Started), Some(AgentState::Awaiting)),
+            change("r#1", None, Some(AgentState::Started)),
+        ],
+        "net changes only"
+    );
+}
+
+#[tokio::test]
+async fn dnd_huge_duration_saturates() {
+    let state = fresh_state();
+    handler::process(IncomingMessage::Dnd { enabled: true, duration_secs: Some(u64::MAX) }, &state).await;
+    let mut s = state.lock().await;
+    assert!(s.dnd_active(), "a huge duration must not wrap into an expired one");
+    assert_eq!(s.take_dnd_events(), vec![DndEvent::Started { reason: DndReason::Manual, until: Some(u64::MAX) }]);
+}
+
 #[tokio::test]
 async fn scenario_full_lifecycle() {
     let state = fresh_state();
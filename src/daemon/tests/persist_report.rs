@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use argus_agenticus::config::{ClockTime, Weekday};
+use argus_agenticus::persist::{self, Snapshot};
+use argus_agenticus::protocol::{AgentState, DndReason, SessionMetadata};
+use argus_agenticus::state::{DndEvent, StateManager};
+
+fn temp_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join("argus-test");
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(format!("{}-{}.json", name, std::process::id()))
+}
+
+fn populated() -> StateManager {
+    let mut s = StateManager::new();
+    s.update_state("a#1".into(), AgentState::Awaiting, "Bash".into(), "claude".into());
+    s.update_state("b#1".into(), AgentState::Working, "Edit".into(), "cursor".into());
+    s.update_state("c#1".into(), AgentState::Ended, String::new(), "claude".into());
+    s.update_state("d#1".into(), AgentState::Started, String::new(), "claude".into());
+    s.update_metadata("b#1", SessionMetadata { pid: Some(std::process::id()), ..Default::default() });
+    s.update_metadata("d#1", SessionMetadata { pid: Some(u32::MAX), ..Default::default() });
+    s.update_workspace("a", 2, 1);
+    s.set_auto_focus_config(true, 750);
+    s
+}
+
+#[test]
+fn restore_drops_ended_and_dead_sessions() {
+    let before = populated();
+    let mut after = StateManager::new();
+    let restored = after.restore(before.to_snapshot());
+    assert_eq!(restored, 2);
+
+    let sessions: Vec<String> = after.get_render_data().into_iter().map(|a| a.session).collect();
+    assert_eq!(sessions, vec!["b#1", "a#1"], "a#1 keeps its workspace placement");
+    assert_eq!(after.focus_delay_ms(), 750);
+    assert_eq!(after.get_history("a#1").unwrap().len(), 1);
+}
+
+#[test]
+fn restore_keeps_state_since_and_queue() {
+    let before = populated();
+    let since = before.get_render_data().iter().find(|a| a.session == "a#1").unwrap().state_since;
+
+    let mut after = StateManager::new();
+    after.restore(before.to_snapshot());
+    let agent = after.get_render_data().into_iter().find(|a| a.session == "a#1").unwrap();
+    assert_eq!(agent.state, AgentState::Awaiting);
+    assert_eq!(agent.state_since, since);
+
+    after.set_idle(true);
+    assert!(after.should_auto_focus(), "restored awaiting session is queued");
+    assert_eq!(after.next_awaiting().as_deref(), Some("a#1"));
+}
+
+#[test]
+fn restore_keeps_manual_dnd_until_it_expires() {
+    let (day, time) = (Weekday::Mon, ClockTime::new(12, 0).unwrap());
+    let mut before = StateManager::new();
+    before.set_dnd(true, Some(Duration::from_secs(3600)));
+    let mut after = StateManager::new();
+    after.restore(before.to_snapshot());
+    after.refresh_dnd(day, time);
+    assert!(after.dnd_active(), "timed manual dnd survives a restart");
+    assert!(matches!(after.take_dnd_events()[..], [DndEvent::Started { reason: DndReason::Manual, until: Some(_) }]));
+
+    before.set_dnd(true, None);
+    let mut after = StateManager::new();
+    after.restore(before.to_snapshot());
+    after.refresh_dnd(day, time);
+    assert!(after.dnd_active(), "open-ended manual dnd survives a restart");
+
+    let expired = Snapshot { dnd_manual: true, dnd_until: Some(1), ..before.to_snapshot() };
+    let mut after = StateManager::new();
+    after.restore(expired);
+    after.refresh_dnd(day, time);
+    assert!(!after.dnd_active(), "an expiry passed while down is dropped");
+
+    before.set_dnd(false, None);
+    assert!(before.to_snapshot().quiet_overridden);
+    let mut after = StateManager::new();
+    after.restore(before.to_snapshot());
+    assert!(after.to_snapshot().quiet_overridden);
+}
+
+#[tokio::test]
+async fn save_and_load_roundtrip() {
+    let path = temp_file("roundtrip");
+    let snapshot = populated().to_snapshot();
+    persist::save(&path, &snapshot).await.unwrap();
+    assert_eq!(persist::load(&path).await, Some(snapshot));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn load_ignores_snapshot_from_before_boot() {
+    let path = temp_file("preboot");
+    let snapshot = Snapshot { saved_at: 1, ..populated().to_snapshot() };
+    persist::save(&path, &snapshot).await.unwrap();
+    assert_eq!(persist::load(&path).await, None);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn load_ignores_missing_and_malformed_files() {
+    let path = temp_file("malformed");
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(persist::load(&path).await, None);
+
+    std::fs::write(&path, b"{not json").unwrap();
+    assert_eq!(persist::load(&path).await, None);
+    let _ = std::fs::remove_file(&path);
+}
@@ -1,22 +1,136 @@
 use std::sync::Arc;
 
-use argus_agenticus::protocol::{AgentInfo, AgentState, IncomingMessage, OutgoingMessage};
+use argus_agenticus::protocol::{
+    AgentInfo, AgentState, ClientKind, Envelope, ErrorCode, IncomingMessage, LifecycleEvent, Location,
+    OutgoingMessage, Reply, RequestId, SessionMetadata, SessionSnapshot, Topic, Transition, PROTOCOL_VERSION,
+};
+
+#[test]
+fn deserialize_hello() {
+    let json = r#"{"type":"hello","protocol_version":1,"client":"extension","capabilities":["render"]}"#;
+    let msg: IncomingMessage = serde_json::from_str(json).unwrap();
+    match msg {
+        IncomingMessage::Hello { protocol_version, client, capabilities } => {
+            assert_eq!(protocol_version, 1);
+            assert_eq!(client, ClientKind::Extension);
+            assert_eq!(capabilities, vec!["render".to_string()]);
+        }
+        other => panic!("expected Hello, got {other:?}"),
+    }
+}
+
+#[test]
+fn deserialize_hello_defaults() {
+    let json = r#"{"type":"hello","protocol_version":1,"client":"statusbar"}"#;
+    let msg: IncomingMessage = serde_json::from_str(json).unwrap();
+    match msg {
+        IncomingMessage::Hello { client, capabilities, .. } => {
+            assert_eq!(client, ClientKind::Other);
+            assert!(capabilities.is_empty());
+        }
+        other => panic!("expected Hello, got {other:?}"),
+    }
+}
+
+#[test]
+fn serialize_welcome() {
+    let msg = OutgoingMessage::Welcome {
+        protocol_version: PROTOCOL_VERSION,
+        min_protocol_version: 1,
+        daemon_version: "0.1.0".to_string(),
+        features: vec!["auto_focus".to_string()],
+    };
+    let v: serde_json::Value = serde_json::to_value(&msg).unwrap();
+    assert_eq!(v["type"], "welcome");
+    assert_eq!(v["protocol_version"], PROTOCOL_VERSION);
+    assert_eq!(v["min_protocol_version"], 1);
+    assert_eq!(v["daemon_version"], "0.1.0");
+    assert_eq!(v["features"][0], "auto_focus");
+}
+
+#[test]
+fn serialize_refused() {
+    let msg = OutgoingMessage::Refused {
+        reason: "too old".to_string(),
+        protocol_version: 2,
+        min_protocol_version: 2,
+    };
+    let v: serde_json::Value = serde_json::to_value(&msg).unwrap();
+    assert_eq!(v["type"], "refused");
+    assert_eq!(v["reason"], "too old");
+    assert_eq!(v["min_protocol_version"], 2);
+}
 
 #[test]
 fn deserialize_state() {
     let json = r#"{"type":"state","session":"p#1","state":"started","tool":"bash","agent_type":"claude"}"#;
     let msg: IncomingMessage = serde_json::from_str(json).unwrap();
     match msg {
-        IncomingMessage::State { session, state, tool, agent_type } => {
+        IncomingMessage::State { session, state, tool, agent_type, event, seq, parent_session, group, meta, .. } => {
             assert_eq!(session, "p#1");
             assert_eq!(state, AgentState::Started);
             assert_eq!(tool, "bash");
             assert_eq!(agent_type, "claude");
+            assert!(event.is_none());
+            assert!(seq.is_none());
+            assert!(parent_session.is_none());
+            assert!(group.is_none());
+            assert_eq!(*meta, SessionMetadata::default());
         }
         other => panic!("expected State, got {other:?}"),
     }
 }
 
+#[test]
+fn deserialize_state_with_metadata() {
+    let json = r#"{"type":"state","session":"p#1","state":"working","tool":"Bash","cwd":"/home/u/p","git_branch":"main","pid":4242,"terminal":"zellij","model":"opus","agent_session_id":"abc-123"}"#;
+    match IncomingMessage::parse(json).unwrap() {
+        IncomingMessage::State { meta, .. } => {
+            assert_eq!(meta.cwd.as_deref(), Some("/home/u/p"));
+            assert_eq!(meta.git_branch.as_deref(), Some("main"));
+            assert_eq!(meta.pid, Some(4242));
+            assert_eq!(meta.terminal.as_deref(), Some("zellij"));
+            assert_eq!(meta.model.as_deref(), Some("opus"));
+            assert_eq!(meta.agent_session_id.as_deref(), Some("abc-123"));
+        }
+        other => panic!("expected State, got {other:?}"),
+    }
+}
+
+#[test]
+fn serialize_agent_info_omits_missing_metadata() {
+    let agent = AgentInfo {
+        session: "p#1".to_string(),
+        state: AgentState::Working,
+        focused: false,
+        group: 0,
+        group_key: "p".into(),
+        group_label: None,
+        agent_type: Arc::from("claude"),
+        state_since: 1_700_000_000_000,
+        parent_session: None,
+        aggregate_state: None,
+        meta: SessionMetadata { git_branch: Some("main".to_string()), ..Default::default() },
+    };
+    let v = serde_json::to_value(&agent).unwrap();
+    assert_eq!(v["git_branch"], "main");
+    assert!(v.get("cwd").is_none());
+    assert!(v.get("pid").is_none());
+}
+
+#[test]
+fn metadata_merge_keeps_unreported_fields() {
+    let mut meta = SessionMetadata {
+        cwd: Some("/a".to_string()),
+        git_branch: Some("main".to_string()),
+        ..Default::default()
+    };
+    meta.merge(SessionMetadata { git_branch: Some("fix".to_string()), pid: Some(7), ..Default::default() });
+    assert_eq!(meta.cwd.as_deref(), Some("/a"));
+    assert_eq!(meta.git_branch.as_deref(), Some("fix"));
+    assert_eq!(meta.pid, Some(7));
+}
+
 #[test]
 fn deserialize_state_default_agent_type() {
     let json = r#"{"type":"state","session":"p#1","state":"awaiting","tool":"bash"}"#;
@@ -140,17 +254,25 @@ fn deserialize_auto_focus_config() {
 #[test]
 fn serialize_render() {
     let msg = OutgoingMessage::Render {
+        seq: 7,
         agents: vec![AgentInfo {
             session: "proj#1".to_string(),
             state: AgentState::Started,
             focused: true,
             group: 0,
+            group_key: "proj".into(),
+            group_label: None,
             agent_type: Arc::from("claude"),
+            state_since: 1_700_000_000_000,
+            parent_session: None,
+            aggregate_state: None,
+            meta: Default::default(),
         }],
     };
     let json = serde_json::to_string(&msg).unwrap();
     let v: serde_json::Value = serde_json::from_str(&json).unwrap();
     assert_eq!(v["type"], "render");
+    assert_eq!(v["seq"], 7);
     assert_eq!(v["agents"][0]["session"], "proj#1");
     assert_eq!(v["agents"][0]["state"], "started");
     assert_eq!(v["agents"][0]["focused"], true);
@@ -180,10 +302,21 @@ fn serialize_auto_focus() {
 
 #[test]
 fn serialize_return_workspace() {
-    let msg = OutgoingMessage::ReturnWorkspace;
+    let msg = OutgoingMessage::ReturnWorkspace { destination: None };
     let json = serde_json::to_string(&msg).unwrap();
     let v: serde_json::Value = serde_json::from_str(&json).unwrap();
     assert_eq!(v["type"], "return_workspace");
+    assert!(v["destination"].is_null());
+
+    let destination = Location { workspace: 3, monitor: 1, window: Some("2841".into()) };
+    let v = serde_json::to_value(OutgoingMessage::ReturnWorkspace { destination: Some(destination) }).unwrap();
+    assert_eq!(v["destination"], serde_json::json!({"workspace": 3, "monitor": 1, "window": "2841"}));
+}
+
+#[test]
+fn parse_location() {
+    let msg = IncomingMessage::parse(r#"{"type":"location","workspace":2}"#).unwrap();
+    assert!(matches!(msg, IncomingMessage::Location { workspace: 2, monitor: 0, window: None }));
 }
 
 #[test]
@@ -221,6 +354,66 @@ fn deserialize_window_closed() {
     }
 }
 
+#[test]
+fn deserialize_list_sessions() {
+    let msg: IncomingMessage = serde_json::from_str(r#"{"type":"list_sessions"}"#).unwrap();
+    assert!(matches!(msg, IncomingMessage::ListSessions { state: None }));
+
+    let json = r#"{"type":"list_sessions","state":"awaiting"}"#;
+    let msg: IncomingMessage = serde_json::from_str(json).unwrap();
+    assert!(matches!(msg, IncomingMessage::ListSessions { state: Some(AgentState::Awaiting) }));
+}
+
+#[test]
+fn deserialize_get_session() {
+    let json = r#"{"type":"get_session","session":"proj#1"}"#;
+    let msg: IncomingMessage = serde_json::from_str(json).unwrap();
+    match msg {
+        IncomingMessage::GetSession { session } => assert_eq!(session, "proj#1"),
+        other => panic!("expected GetSession, got {other:?}"),
+    }
+}
+
+#[test]
+fn serialize_sessions() {
+    let msg = OutgoingMessage::Sessions {
+        sessions: vec![SessionSnapshot {
+            agent: AgentInfo {
+                session: "proj#1".to_string(),
+                state: AgentState::Awaiting,
+                focused: false,
+                group: 0,
+                group_key: "proj".into(),
+                group_label: None,
+                agent_type: Arc::from("claude"),
+                state_since: 1_700_000_000_000,
+                parent_session: None,
+                aggregate_state: None,
+                meta: Default::default(),
+            },
+            tool: "Bash".to_string(),
+            last_activity_at: 1_700_000_000_000,
+            ended_at: None,
+        }],
+    };
+    let v: serde_json::Value = serde_json::to_value(&msg).unwrap();
+    assert_eq!(v["type"], "sessions");
+    assert_eq!(v["sessions"][0]["session"], "proj#1");
+    assert_eq!(v["sessions"][0]["state"], "awaiting");
+    assert_eq!(v["sessions"][0]["tool"], "Bash");
+    assert_eq!(v["sessions"][0]["last_activity_at"], 1_700_000_000_000u64);
+    assert!(v["sessions"][0]["ended_at"].is_null());
+}
+
+#[test]
+fn serialize_session_missing() {
+    let msg = OutgoingMessage::Session { session: "x#1".to_string(), snapshot: None };
+    let v: serde_json::Value = serde_json::to_value(&msg).unwrap();
+    assert_eq!(v["type"], "session");
+    assert_eq!(v["session"], "x#1");
+    assert!(v["snapshot"].is_null());
+}
+
 #[test]
 fn invalid_json_error() {
     let result = serde_json::from_str::<IncomingMessage>("not json at all");
@@ -240,3 +433,328 @@ fn missing_required_field() {
     let result = serde_json::from_str::<IncomingMessage>(json);
     assert!(result.is_err());
 }
+
+#[test]
+fn parse_valid_message() {
+    let msg = IncomingMessage::parse(r#"{"type":"click","session":"p#1"}"#).unwrap();
+    assert!(matches!(msg, IncomingMessage::Click { .. }));
+}
+
+#[test]
+fn parse_invalid_json() {
+    let err = IncomingMessage::parse("not json at all").unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidJson);
+    assert_eq!(err.message_type, None);
+}
+
+#[test]
+fn parse_unknown_type_unsupported() {
+    let err = IncomingMessage::parse(r#"{"type":"from_the_future","x":1}"#).unwrap_err();
+    assert_eq!(err.code, ErrorCode::Unsupported);
+    assert_eq!(err.message_type.as_deref(), Some("from_the_future"));
+}
+
+#[test]
+fn parse_missing_type() {
+    let err = IncomingMessage::parse(r#"{"session":"p#1"}"#).unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidMessage);
+    assert_eq!(err.message_type, None);
+}
+
+#[test]
+fn parse_known_type_bad_fields() {
+    let err = IncomingMessage::parse(r#"{"type":"state","session":"p#1"}"#).unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidMessage);
+    assert_eq!(err.message_type.as_deref(), Some("state"));
+    assert!(err.message.contains("state"), "message: {}", err.message);
+}
+
+#[test]
+fn types_cover_every_variant() {
+    let samples = vec![
+        IncomingMessage::Hello { protocol_version: 1, client: ClientKind::Cli, capabilities: vec![] },
+        IncomingMessage::State {
+            session: "s".into(),
+            state: AgentState::Started,
+            tool: "t".into(),
+            agent_type: "claude".into(),
+            event: None,
+            seq: None,
+            parent_session: None,
+            group: None,
+            group_label: None,
+            meta: Default::default(),
+        },
+        IncomingMessage::WindowFocus { title: "t".into(), agent_type: String::new() },
+        IncomingMessage::SessionWorkspace { session: "s".into(), workspace: 0, monitor: 0 },
+        IncomingMessage::Location { workspace: 0, monitor: 0, window: None },
+        IncomingMessage::Click { session: "s".into() },
+        IncomingMessage::FocusNext,
+        IncomingMessage::FocusPrev,
+        IncomingMessage::FocusNextInState { state: AgentState::Awaiting },
+        IncomingMessage::FocusIndex { n: 1 },
+        IncomingMessage::FocusGroup { group: "g".into() },
+        IncomingMessage::FocusLast,
+        IncomingMessage::IdleStatus { idle: true },
+        IncomingMessage::AutoFocusConfig { enabled: true, focus_delay_ms: 0 },
+        IncomingMessage::Dnd { enabled: true, duration_secs: None },
+        IncomingMessage::ClearAgents,
+        IncomingMessage::MarkAllStarted,
+        IncomingMessage::WindowClosed { session: "s".into() },
+        IncomingMessage::ListSessions { state: None },
+        IncomingMessage::GetSession { session: "s".into() },
+        IncomingMessage::SessionHistory { session: "s".into() },
+        IncomingMessage::Subscribe { topics: vec![Topic::Render] },
+        IncomingMessage::Unsubscribe { topics: vec![Topic::Render] },
+        IncomingMessage::Resync,
+        IncomingMessage::Batch { messages: vec![IncomingMessage::FocusNext] },
+    ];
+    assert_eq!(samples.len(), IncomingMessage::TYPES.len());
+    for msg in &samples {
+        let v = serde_json::to_value(msg).unwrap();
+        let t = v["type"].as_str().unwrap();
+        assert!(IncomingMessage::TYPES.contains(&t), "'{t}' missing from TYPES");
+    }
+}
+
+#[test]
+fn serialize_error() {
+    let msg = OutgoingMessage::Error {
+        code: ErrorCode::Unsupported,
+        message_type: Some("from_the_future".to_string()),
+        message: "unsupported message type 'from_the_future'".to_string(),
+    };
+    let v: serde_json::Value = serde_json::to_value(&msg).unwrap();
+    assert_eq!(v["type"], "error");
+    assert_eq!(v["code"], "unsupported");
+    assert_eq!(v["message_type"], "from_the_future");
+    assert!(v["message"].is_string());
+}
+
+#[test]
+fn deserialize_subscribe() {
+    let json = r#"{"type":"subscribe","topics":["render","auto_focus","return_workspace","focus","lifecycle"]}"#;
+    let msg: IncomingMessage = serde_json::from_str(json).unwrap();
+    match msg {
+        IncomingMessage::Subscribe { topics } => {
+            assert_eq!(
+                topics,
+                vec![Topic::Render, Topic::AutoFocus, Topic::ReturnWorkspace, Topic::Focus, Topic::Lifecycle]
+            );
+        }
+        other => panic!("expected Subscribe, got {other:?}"),
+    }
+}
+
+#[test]
+fn deserialize_unsubscribe_unknown_topic() {
+    let err = IncomingMessage::parse(r#"{"type":"unsubscribe","topics":["weather"]}"#).unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidMessage);
+    assert_eq!(err.message_type.as_deref(), Some("unsubscribe"));
+}
+
+#[test]
+fn serialize_subscribed() {
+    let msg = OutgoingMessage::Subscribed { topics: vec![Topic::Render, Topic::Lifecycle] };
+    let v: serde_json::Value = serde_json::to_value(&msg).unwrap();
+    assert_eq!(v["type"], "subscribed");
+    assert_eq!(v["topics"], serde_json::json!(["render", "lifecycle"]));
+}
+
+#[test]
+fn serialize_lifecycle() {
+    let msg = OutgoingMessage::Lifecycle {
+        session: "proj#1".to_string(),
+        event: LifecycleEvent::Changed,
+        previous: Some(AgentState::Working),
+        state: Some(AgentState::Awaiting),
+    };
+    let v: serde_json::Value = serde_json::to_value(&msg).unwrap();
+    assert_eq!(v["type"], "lifecycle");
+    assert_eq!(v["session"], "proj#1");
+    assert_eq!(v["event"], "changed");
+    assert_eq!(v["previous"], "working");
+    assert_eq!(v["state"], "awaiting");
+}
+
+#[test]
+fn topics_of_broadcasts() {
+    let render = OutgoingMessage::Render { seq: 0, agents: vec![] };
+    assert_eq!(render.topic(), Some(Topic::Render));
+    assert_eq!(OutgoingMessage::ReturnWorkspace { destination: None }.topic(), Some(Topic::ReturnWorkspace));
+    let auto = OutgoingMessage::AutoFocus { session: "s".into(), agent_type: "claude".into() };
+    assert_eq!(auto.topic(), Some(Topic::AutoFocus));
+    let sessions = OutgoingMessage::Sessions { sessions: vec![] };
+    assert_eq!(sessions.topic(), None);
+}
+
+#[test]
+fn deserialize_resync() {
+    let msg: IncomingMessage = serde_json::from_str(r#"{"type":"resync"}"#).unwrap();
+    assert!(matches!(msg, IncomingMessage::Resync));
+}
+
+#[test]
+fn serialize_render_delta() {
+    let msg = OutgoingMessage::RenderDelta {
+        seq: 5,
+        base_seq: 4,
+        added: vec![],
+        changed: vec![AgentInfo {
+            session: "proj#1".to_string(),
+            state: AgentState::Awaiting,
+            focused: false,
+            group: 0,
+            group_key: "proj".into(),
+            group_label: None,
+            agent_type: Arc::from("claude"),
+            state_since: 1_700_000_000_000,
+            parent_session: None,
+            aggregate_state: None,
+            meta: Default::default(),
+        }],
+        removed: vec!["proj#2".to_string()],
+        order: None,
+    };
+    let v: serde_json::Value = serde_json::to_value(&msg).unwrap();
+    assert_eq!(v["type"], "render_delta");
+    assert_eq!(v["seq"], 5);
+    assert_eq!(v["base_seq"], 4);
+    assert_eq!(v["changed"][0]["state"], "awaiting");
+    assert_eq!(v["removed"][0], "proj#2");
+    assert!(v["order"].is_null());
+    assert_eq!(msg.topic(), Some(Topic::RenderDelta));
+}
+
+#[test]
+fn envelope_without_id() {
+    let env = Envelope::parse(r#"{"type":"click","session":"p#1"}"#).unwrap();
+    assert_eq!(env.id, None);
+    assert!(matches!(env.message, IncomingMessage::Click { .. }));
+}
+
+#[test]
+fn envelope_numeric_id() {
+    let env = Envelope::parse(r#"{"type":"focus_next","id":42}"#).unwrap();
+    assert_eq!(env.id, Some(RequestId::Number(42)));
+    assert!(matches!(env.message, IncomingMessage::FocusNext));
+}
+
+#[test]
+fn envelope_string_id() {
+    let json = r#"{"id":"req-1","type":"session_workspace","session":"p#1","workspace":2,"monitor":1}"#;
+    let env = Envelope::parse(json).unwrap();
+    assert_eq!(env.id, Some(RequestId::Text("req-1".to_string())));
+    match env.message {
+        IncomingMessage::SessionWorkspace { workspace, monitor, .. } => {
+            assert_eq!(workspace, 2);
+            assert_eq!(monitor, 1);
+        }
+        other => panic!("expected SessionWorkspace, got {other:?}"),
+    }
+}
+
+#[test]
+fn envelope_error_keeps_id() {
+    let err = Envelope::parse(r#"{"type":"teleport","id":7}"#).unwrap_err();
+    assert_eq!(err.code, ErrorCode::Unsupported);
+    assert_eq!(err.id, Some(RequestId::Number(7)));
+
+    let err = Envelope::parse(r#"{"type":"click","id":"x"}"#).unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidMessage);
+    assert_eq!(err.id, Some(RequestId::Text("x".to_string())));
+}
+
+#[test]
+fn serialize_reply_with_id() {
+    let reply = Reply {
+        id: Some(RequestId::Number(3)),
+        message: OutgoingMessage::Focus { session: "p#1".to_string(), agent_type: "claude".to_string() },
+    };
+    let v: serde_json::Value = serde_json::to_value(&reply).unwrap();
+    assert_eq!(v["id"], 3);
+    assert_eq!(v["type"], "focus");
+    assert_eq!(v["session"], "p#1");
+}
+
+#[test]
+fn serialize_reply_without_id() {
+    let reply = Reply { id: None, message: OutgoingMessage::Ack };
+    let v: serde_json::Value = serde_json::to_value(&reply).unwrap();
+    assert_eq!(v, serde_json::json!({"type": "ack"}));
+}
+
+#[test]
+fn deserialize_batch() {
+    let json = r#"{"type":"batch","messages":[{"type":"session_workspace","session":"p","workspace":2},{"type":"idle_status","idle":false}]}"#;
+    match IncomingMessage::parse(json).unwrap() {
+        IncomingMessage::Batch { messages } => {
+            assert_eq!(messages.len(), 2);
+            assert!(matches!(messages[0], IncomingMessage::SessionWorkspace { workspace: 2, .. }));
+            assert!(matches!(messages[1], IncomingMessage::IdleStatus { idle: false }));
+        }
+        other => panic!("expected Batch, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_batch_with_bad_member_rejects_whole_batch() {
+    let json = r#"{"type":"batch","id":4,"messages":[{"type":"focus_next"},{"type":"idle_status"}]}"#;
+    let err = Envelope::parse(json).unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidMessage);
+    assert_eq!(err.message_type.as_deref(), Some("batch"));
+    assert_eq!(err.id, Some(RequestId::Number(4)));
+}
+
+#[test]
+fn deserialize_state_with_event() {
+    let json = r#"{"type":"state","session":"p#1","state":"awaiting","tool":"Bash","event":"PermissionRequest"}"#;
+    match IncomingMessage::parse(json).unwrap() {
+        IncomingMessage::State { event, .. } => assert_eq!(event.as_deref(), Some("PermissionRequest")),
+        other => panic!("expected State, got {other:?}"),
+    }
+}
+
+#[test]
+fn serialize_history() {
+    let msg = OutgoingMessage::History {
+        session: "p#1".to_string(),
+        transitions: Some(vec![Transition {
+            at: 1_700_000_000_000,
+            previous: None,
+            state: AgentState::Started,
+            tool: String::new(),
+            event: Some("SessionStart".to_string()),
+        }]),
+    };
+    let v = serde_json::to_value(&msg).unwrap();
+    assert_eq!(v["type"], "history");
+    assert_eq!(v["transitions"][0]["previous"], serde_json::Value::Null);
+    assert_eq!(v["transitions"][0]["state"], "started");
+    assert_eq!(v["transitions"][0]["event"], "SessionStart");
+}
+
+#[test]
+fn metadata_new_pid_clears_stale_start_time() {
+    let mut meta = SessionMetadata { pid: Some(7), pid_start_time: Some(100), ..Default::default() };
+    meta.merge(SessionMetadata { pid: Some(7), ..Default::default() });
+    assert_eq!(meta.pid_start_time, Some(100), "same pid keeps its start time");
+    meta.merge(SessionMetadata { pid: Some(8), ..Default::default() });
+    assert_eq!(meta.pid_start_time, None);
+    meta.merge(SessionMetadata { pid: Some(9), pid_start_time: Some(200), ..Default::default() });
+    assert_eq!((meta.pid, meta.pid_start_time), (Some(9), Some(200)));
+}
+
+#[test]
+fn transition_table() {
+    use AgentState::*;
+    for to in [Started, Awaiting, Working, Processing, Completed, Ended] {
+        assert!(Working.accepts(to));
+        assert!(Awaiting.accepts(to));
+    }
+    assert!(Ended.accepts(Started));
+    assert!(Ended.accepts(Processing));
+    for to in [Awaiting, Working, Completed, Ended] {
+        assert!(!Ended.accepts(to), "ended -> {to:?}");
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use argus_agenticus::protocol::{AgentInfo, AgentState, OutgoingMessage};
+use argus_agenticus::publish;
+
+fn agent(session: &str, state: AgentState, group: u32) -> AgentInfo {
+    AgentInfo {
+        session: session.to_string(),
+        state,
+        focused: false,
+        group,
+        group_key: session.split('#').next().unwrap().to_string(),
+        group_label: None,
+        agent_type: Arc::from("claude"),
+        state_since: 1_700_000_000_000,
+        parent_session: None,
+        aggregate_state: None,
+        meta: Default::default(),
+    }
+}
+
+#[test]
+fn delta_unchanged_is_none() {
+    let agents = vec![agent("a#1", AgentState::Started, 0), agent("b#1", AgentState::Working, 1)];
+    assert!(publish::delta(2, 1, &agents, &agents).is_none());
+}
+
+#[test]
+fn delta_changed_state() {
+    let base = vec![agent("a#1", AgentState::Started, 0), agent("b#1", AgentState::Working, 1)];
+    let next = vec![agent("a#1", AgentState::Awaiting, 0), agent("b#1", AgentState::Working, 1)];
+    match publish::delta(2, 1, &base, &next) {
+        Some(OutgoingMessage::RenderDelta { seq, base_seq, added, changed, removed, order }) => {
+            assert_eq!(seq, 2);
+            assert_eq!(base_seq, 1);
+            assert!(added.is_empty());
+            assert_eq!(changed.len(), 1);
+            assert_eq!(changed[0].session, "a#1");
+            assert_eq!(changed[0].state, AgentState::Awaiting);
+            assert!(removed.is_empty());
+            assert!(order.is_none());
+        }
+        other => panic!("expected RenderDelta, got {other:?}"),
+    }
+}
+
+#[test]
+fn delta_added_and_removed() {
+    let base = vec![agent("a#1", AgentState::Started, 0), agent("b#1", AgentState::Working, 1)];
+    let next = vec![agent("a#1", AgentState::Started, 0), agent("c#1", AgentState::Started, 1)];
+    match publish::delta(3, 2, &base, &next) {
+        Some(OutgoingMessage::RenderDelta { added, changed, removed, order, .. }) => {
+            assert_eq!(added.len(), 1);
+            assert_eq!(added[0].session, "c#1");
+            assert!(changed.is_empty());
+            assert_eq!(removed, vec!["b#1".to_string()]);
+            assert_eq!(order, Some(vec!["a#1".to_string(), "c#1".to_string()]));
+        }
+        other => panic!("expected RenderDelta, got {other:?}"),
+    }
+}
+
+#[test]
+fn delta_reorder_only() {
+    let base = vec![agent("a#1", AgentState::Started, 0), agent("b#1", AgentState::Started, 1)];
+    let next = vec![agent("b#1", AgentState::Started, 1), agent("a#1", AgentState::Started, 0)];
+    match publish::delta(2, 1, &base, &next) {
+        Some(OutgoingMessage::RenderDelta { added, changed, removed, order, .. }) => {
+            assert!(added.is_empty());
+            assert!(changed.is_empty());
+            assert!(removed.is_empty());
+            assert_eq!(order, Some(vec!["b#1".to_string(), "a#1".to_string()]));
+        }
+        other => panic!("expected RenderDelta, got {other:?}"),
+    }
+}
+
+#[test]
+fn delta_from_empty_base() {
+    let next = vec![agent("a#1", AgentState::Started, 0)];
+    match publish::delta(1, 0, &[], &next) {
+        Some(OutgoingMessage::RenderDelta { added, removed, .. }) => {
+            assert_eq!(added.len(), 1);
+            assert!(removed.is_empty());
+        }
+        other => panic!("expected RenderDelta, got {other:?}"),
+    }
+}
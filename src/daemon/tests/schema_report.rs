@@ -0,0 +1,139 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use argus_agenticus::handler;
+use argus_agenticus::protocol::{self, ClientKind, Envelope, IncomingMessage, OutgoingMessage, Reply};
+use argus_agenticus::state::StateManager;
+use tokio::sync::Mutex;
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+fn fixture_lines(name: &str) -> Vec<serde_json::Value> {
+    std::fs::read_to_string(fixture_path(name))
+        .unwrap()
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| serde_json::from_str(l).unwrap_or_else(|e| panic!("{name}: {e}: {l}")))
+        .collect()
+}
+
+fn schema_value() -> serde_json::Value {
+    serde_json::to_value(protocol::schema()).unwrap()
+}
+
+fn validator() -> jsonschema::Validator {
+    jsonschema::validator_for(&schema_value()).unwrap()
+}
+
+fn variant_types(schema: &serde_json::Value, definition: &str) -> BTreeSet<String> {
+    schema["definitions"][definition]["oneOf"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v["properties"]["type"]["enum"][0].as_str().unwrap().to_string())
+        .collect()
+}
+
+fn fixture_types(name: &str) -> BTreeSet<String> {
+    fixture_lines(name).iter().map(|v| v["type"].as_str().unwrap().to_string()).collect()
+}
+
+/// Regenerate with `UPDATE_FIXTURES=1 cargo test --test schema_report`.
+#[test]
+fn schema_matches_golden() {
+    let path = fixture_path("schema.json");
+    let generated = serde_json::to_string_pretty(&protocol::schema()).unwrap() + "\n";
+    if std::env::var_os("UPDATE_FIXTURES").is_some() {
+        std::fs::write(&path, &generated).unwrap();
+    }
+    let golden = std::fs::read_to_string(&path).unwrap();
+    assert!(golden == generated, "schema.json is stale, rerun with UPDATE_FIXTURES=1");
+}
+
+#[test]
+fn schema_lists_every_incoming_type() {
+    let expected: BTreeSet<String> = IncomingMessage::TYPES.iter().map(|t| t.to_string()).collect();
+    assert_eq!(variant_types(&schema_value(), "IncomingMessage"), expected);
+}
+
+#[test]
+fn schema_carries_protocol_version() {
+    let schema = schema_value();
+    assert_eq!(schema["x-protocol-version"], protocol::PROTOCOL_VERSION);
+    assert_eq!(schema["x-min-protocol-version"], protocol::MIN_PROTOCOL_VERSION);
+    for name in ["IncomingMessage", "OutgoingMessage", "AgentInfo", "AgentState"] {
+        assert!(schema["definitions"].get(name).is_some(), "{name} missing from definitions");
+    }
+}
+
+#[test]
+fn incoming_fixtures_cover_every_type() {
+    let expected: BTreeSet<String> = IncomingMessage::TYPES.iter().map(|t| t.to_string()).collect();
+    assert_eq!(fixture_types("incoming.jsonl"), expected);
+}
+
+#[test]
+fn outgoing_fixtures_cover_every_type() {
+    assert_eq!(fixture_types("outgoing.jsonl"), variant_types(&schema_value(), "OutgoingMessage"));
+}
+
+#[test]
+fn incoming_fixtures_parse() {
+    for v in fixture_lines("incoming.jsonl") {
+        let line = v.to_string();
+        if let Err(e) = Envelope::parse(&line) {
+            panic!("{line}: {e:?}");
+        }
+    }
+}
+
+#[test]
+fn outgoing_fixtures_roundtrip() {
+    for v in fixture_lines("outgoing.jsonl") {
+        let reply: Reply = serde_json::from_value(v.clone()).unwrap_or_else(|e| panic!("{v}: {e}"));
+        assert_eq!(serde_json::to_value(&reply).unwrap(), v);
+    }
+}
+
+#[tokio::test]
+async fn refused_fixture_matches_handler() {
+    let fixture = fixture_lines("outgoing.jsonl").into_iter().find(|v| v["type"] == "refused").unwrap();
+    let version = fixture["min_protocol_version"].as_u64().unwrap() as u32 - 1;
+    let state = Arc::new(Mutex::new(StateManager::new()));
+    let hello = IncomingMessage::Hello { protocol_version: version, client: ClientKind::Cli, capabilities: vec![] };
+    let fx = handler::process(hello, &state).await;
+    let [refused @ OutgoingMessage::Refused { .. }] = fx.replies.as_slice() else {
+        panic!("expected Refused reply, got {:?}", fx.replies);
+    };
+    assert_eq!(serde_json::to_value(refused).unwrap(), fixture);
+}
+
+#[test]
+fn fixtures_validate_against_schema() {
+    let validator = validator();
+    for name in ["incoming.jsonl", "outgoing.jsonl"] {
+        for v in fixture_lines(name) {
+            let errors: Vec<String> = validator.iter_errors(&v).map(|e| e.to_string()).collect();
+            assert!(errors.is_empty(), "{name}: {v}: {errors:?}");
+        }
+    }
+}
+
+#[test]
+fn schema_rejects_malformed_messages() {
+    let validator = validator();
+    for line in [
+        r#"{"type":"teleport"}"#,
+        r#"{"type":"state","session":"p#1"}"#,
+        r#"{"type":"idle_status","idle":"yes"}"#,
+        r#"{"type":"subscribe","topics":["weather"]}"#,
+        r#"{"session":"p#1"}"#,
+    ] {
+        let v: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert!(!validator.is_valid(&v), "{line} should be rejected");
+        assert!(Envelope::parse(line).is_err(), "{line} should not parse");
+    }
+}
@@ -3,10 +3,11 @@ use argus_agenticus::alloc_tracker;
 #[global_allocator]
 static GLOBAL: alloc_tracker::TrackingAllocator = alloc_tracker::TrackingAllocator;
 
-use argus_agenticus::protocol::AgentState;
+use argus_agenticus::protocol::{AgentState, SessionMetadata};
 use argus_agenticus::state::{AutoFocusEvent, StateManager};
 
 use std::fmt::Write as FmtWrite;
+use std::collections::HashMap;
 use std::fs;
 use std::sync::Arc;
 use std::time::Instant;
@@ -142,13 +143,16 @@ fn test_focus_next_deterministic() -> bool {
     sm.update_workspace("gamma", 3, 0);
 
     let first = sm.focus_next().unwrap();
-    assert_eq!(first, "alpha#1");
+    assert_eq!(first, "beta#1");
 
     let second = sm.focus_next().unwrap();
-    assert_eq!(second, "gamma#1");
+    assert_eq!(second, "alpha#1");
 
     let third = sm.focus_next().unwrap();
-    assert_eq!(third, "beta#1");
+    assert_eq!(third, "gamma#1");
+
+    let wrapped = sm.focus_next().unwrap();
+    assert_eq!(wrapped, "beta#1");
     true
 }
 
@@ -439,13 +443,150 @@ fn test_stale_cursor_activity_resets_timer() -> bool {
     true
 }
 
+fn test_session_changes_recorded() -> bool {
+    let mut sm = StateManager::new();
+    sm.update_state(s("proj#1"), AgentState::Started, s("bash"), a("claude"));
+    sm.update_state(s("proj#1"), AgentState::Working, s("bash"), a("claude"));
+    sm.update_state(s("proj#1"), AgentState::Working, s("edit"), a("claude"));
+    sm.remove_session("proj#1");
+
+    let changes = sm.take_changes();
+    assert_eq!(changes.len(), 3);
+    assert_eq!(changes[0].previous, None);
+    assert_eq!(changes[0].state, Some(AgentState::Started));
+    assert_eq!(changes[1].previous, Some(AgentState::Started));
+    assert_eq!(changes[1].state, Some(AgentState::Working));
+    assert_eq!(changes[2].previous, Some(AgentState::Working));
+    assert_eq!(changes[2].state, None);
+    assert!(sm.take_changes().is_empty());
+    true
+}
+
+fn test_session_changes_bulk() -> bool {
+    let mut sm = StateManager::new();
+    sm.update_state(s("proj#1"), AgentState::Awaiting, s("bash"), a("claude"));
+    sm.update_state(s("proj#2"), AgentState::Completed, s("bash"), a("claude"));
+    sm.take_changes();
+
+    sm.mark_all_started();
+    sm.update_window_focus("proj - editor", None);
+    let changes = sm.take_changes();
+    assert_eq!(changes.len(), 2);
+    assert!(changes.iter().all(|c| c.state == Some(AgentState::Started)));
+
+    sm.clear_all();
+    let changes = sm.take_changes();
+    assert_eq!(changes.len(), 2);
+    assert!(changes.iter().all(|c| c.state.is_none()));
+    true
+}
+
+fn own_start_time() -> u64 {
+    let stat = std::fs::read_to_string("/proc/self/stat").unwrap();
+    stat.rsplit_once(')').unwrap().1.split_whitespace().nth(19).unwrap().parse().unwrap()
+}
+
+fn test_reap_dead_process() -> bool {
+    let mut child = std::process::Command::new("sleep").arg("60").spawn().unwrap();
+    let mut sm = StateManager::new();
+    let pid = |pid: u32, start: Option<u64>| SessionMetadata { pid: Some(pid), pid_start_time: start, ..Default::default() };
+    for name in ["alive#1", "gone#1", "reused#1", "nopid#1"] {
+        sm.update_state(s(name), AgentState::Awaiting, s("Bash"), a("claude"));
+    }
+    sm.update_metadata("alive#1", pid(std::process::id(), Some(own_start_time())));
+    sm.update_metadata("gone#1", pid(child.id(), None));
+    sm.update_metadata("reused#1", pid(std::process::id(), Some(own_start_time() + 1)));
+    assert!(sm.next_awaiting().is_some());
+
+    assert_eq!(sm.reap_dead(), Some(AutoFocusEvent::Trigger), "reused PID is dead, queue still has work");
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert_eq!(sm.reap_dead(), Some(AutoFocusEvent::Trigger));
+    assert_eq!(sm.reap_dead(), None, "ended sessions are not reaped twice");
+
+    let states: HashMap<String, AgentState> =
+        sm.get_render_data().into_iter().map(|a| (a.session, a.state)).collect();
+    assert_eq!(states["alive#1"], AgentState::Awaiting);
+    assert_eq!(states["nopid#1"], AgentState::Awaiting);
+    assert_eq!(states["gone#1"], AgentState::Ended);
+    assert_eq!(states["reused#1"], AgentState::Ended);
+    let last = sm.get_history("gone#1").unwrap().last().cloned().unwrap();
+    assert_eq!(last.event.as_deref(), Some("process_exit"));
+    true
+}
+
+fn test_focus_next_cursor_survives_changes() -> bool {
+    let mut sm = StateManager::new();
+    for name in ["a#1", "b#1", "c#1", "d#1"] {
+        sm.update_state(s(name), AgentState::Started, s("bash"), a("claude"));
+    }
+    assert_eq!(sm.focus_next().unwrap(), "a#1");
+    assert_eq!(sm.focus_next().unwrap(), "b#1");
+
+    // Removing the current session or adding one behind the cursor must not skip anyone.
+    sm.remove_session("b#1");
+    sm.update_state(s("a0#1"), AgentState::Started, s("bash"), a("claude"));
+    assert_eq!(sm.focus_next().unwrap(), "c#1");
+
+    // An awaiting session takes over without disturbing the started tier's cursor.
+    sm.update_state(s("z#1"), AgentState::Awaiting, s("bash"), a("claude"));
+    assert_eq!(sm.focus_next().unwrap(), "z#1");
+    sm.update_state(s("z#1"), AgentState::Working, s("bash"), a("claude"));
+    assert_eq!(sm.focus_next().unwrap(), "d#1");
+    assert_eq!(sm.focus_next().unwrap(), "a#1");
+    true
+}
+
+fn test_focus_prev_and_next_in_state() -> bool {
+    let mut sm = StateManager::new();
+    sm.update_state(s("alpha#1"), AgentState::Started, s("bash"), a("claude"));
+    sm.update_state(s("beta#1"), AgentState::Started, s("bash"), a("claude"));
+    sm.update_state(s("gamma#1"), AgentState::Started, s("bash"), a("claude"));
+    sm.update_state(s("delta#1"), AgentState::Working, s("bash"), a("claude"));
+    sm.update_workspace("alpha", 2, 0);
+    sm.update_workspace("beta", 1, 0);
+    sm.update_workspace("gamma", 3, 0);
+
+    assert_eq!(sm.focus_prev().as_deref(), Some("gamma#1"), "prev starts from the end");
+    assert_eq!(sm.focus_prev().as_deref(), Some("alpha#1"));
+    assert_eq!(sm.focus_next().as_deref(), Some("gamma#1"), "next and prev share the cursor");
+    assert_eq!(sm.focus_next_in_state(AgentState::Working).as_deref(), Some("delta#1"));
+    assert_eq!(sm.focus_next_in_state(AgentState::Awaiting), None);
+    true
+}
+
+fn test_focus_index_group_last() -> bool {
+    let mut sm = StateManager::new();
+    sm.update_state(s("alpha#1"), AgentState::Started, s("bash"), a("claude"));
+    sm.update_state(s("alpha#2"), AgentState::Awaiting, s("bash"), a("claude"));
+    sm.update_state(s("beta#1"), AgentState::Started, s("bash"), a("claude"));
+    sm.update_workspace("alpha", 2, 0);
+    sm.update_workspace("beta", 1, 0);
+
+    assert_eq!(sm.focus_index(1).as_deref(), Some("beta#1"), "panel order, from 1");
+    assert_eq!(sm.focus_index(0), None);
+    assert_eq!(sm.focus_index(4), None);
+    assert_eq!(sm.focus_group("alpha").as_deref(), Some("alpha#2"), "most urgent in the group");
+    assert_eq!(sm.focus_group("gamma"), None);
+
+    assert_eq!(sm.focus_last(), None, "nothing focused yet");
+    sm.update_window_focus("alpha", None);
+    sm.update_window_focus("beta", None);
+    assert!(sm.focus_last().is_some_and(|s| s.starts_with("alpha#")));
+    sm.update_window_focus("Firefox", None);
+    assert_eq!(sm.focus_last().as_deref(), Some("beta#1"), "from another window, the last agent");
+    sm.remove_session("beta#1");
+    assert!(sm.focus_last().is_some_and(|s| s.starts_with("alpha#")), "removed sessions are skipped");
+    true
+}
+
 fn test_stress_1000() -> bool {
     let mut sm = StateManager::new();
     for i in 0..1000 {
         let session = format!("project_{}#{}", i / 10, i % 10);
         let ws = (i / 10) as u32;
         sm.update_state(session.clone(), AgentState::Started, s("bash"), a("claude"));
-        sm.update_workspace(&session.split('#').next().unwrap().to_string(), ws, 0);
+        sm.update_workspace(session.split('#').next().unwrap(), ws, 0);
     }
 
     let data = sm.get_render_data();
@@ -463,7 +604,8 @@ fn test_stress_1000() -> bool {
 
 #[test]
 fn full_report() {
-    let tests: Vec<(&str, fn() -> bool)> = vec![
+    type Case = (&'static str, fn() -> bool);
+    let tests: Vec<Case> = vec![
         ("update_state_awaiting", test_update_state_awaiting),
         ("update_state_left_awaiting", test_update_state_left_awaiting),
         ("update_state_completed_focused", test_update_state_completed_focused),
@@ -489,6 +631,12 @@ fn full_report() {
         ("stale_cursor_completed_not_removed", test_stale_cursor_completed_not_removed),
         ("stale_cursor_focused_not_removed", test_stale_cursor_focused_not_removed),
         ("stale_cursor_activity_resets_timer", test_stale_cursor_activity_resets_timer),
+        ("session_changes_recorded", test_session_changes_recorded),
+        ("session_changes_bulk", test_session_changes_bulk),
+        ("reap_dead_process", test_reap_dead_process),
+        ("focus_next_cursor_survives_changes", test_focus_next_cursor_survives_changes),
+        ("focus_prev_and_next_in_state", test_focus_prev_and_next_in_state),
+        ("focus_index_group_last", test_focus_index_group_last),
         ("stress_1000_sessions", test_stress_1000),
     ];
 
@@ -505,8 +653,8 @@ fn full_report() {
     writeln!(report).unwrap();
     writeln!(
         report,
-        "{:>2}  {:<36} {:<8} {:<11} {:<8} {}",
-        "#", "Test", "Status", "Time", "Allocs", "Bytes"
+        "{:>2}  {:<36} {:<8} {:<11} {:<8} Bytes",
+        "#", "Test", "Status", "Time", "Allocs"
     ).unwrap();
 
     let mut passed = 0u32;
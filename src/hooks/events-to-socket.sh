@@ -1,4 +1,6 @@
 #!/bin/bash
+# Taken first, so the daemon can drop updates from hooks that lost a race.
+SEQ=$(date +%s%6N)
 INPUT=$(timeout 1 cat 2>/dev/null || echo '{}')
 read -r EVENT TOOL IS_INTERRUPT <<< $(echo "$INPUT" | jq -r '[.hook_event_name // "unknown", .tool_name // "", .is_interrupt // false] | @tsv')
 
@@ -31,8 +33,25 @@ else
     fi
 fi
 
+# Claude Code marks events from inside a Task-tool subagent with its
+# agent_id. Report them as a child session so they leave the parent alone.
+AGENT_ID=$(echo "$INPUT" | jq -r '.agent_id // empty')
+if [ "$AGENT_TYPE" = "claude" ] && [ -n "$AGENT_ID" ]; then
+    PARENT_SESSION="$SESSION"
+    SESSION="${SESSION}-${AGENT_ID:0:8}"
+fi
+
 case "$EVENT" in
   SessionStart|sessionStart)                          STATE="started" ;;
+  SubagentStart|SubagentStop)
+    # Without agent_id these would be mistaken for the parent's own events.
+    [ -z "$AGENT_ID" ] && exit 0
+    if [ "$EVENT" = "SubagentStart" ]; then
+      STATE="started"
+    else
+      STATE="ended"
+    fi
+    ;;
   PreToolUse|preToolUse)                              STATE="working" ;;
   PostToolUseFailure|postToolUseFailure)
     if [ "$IS_INTERRUPT" = "true" ]; then
@@ -53,17 +72,53 @@ case "$EVENT" in
     ;;
 esac
 
+CWD=$(echo "$INPUT" | jq -r '.cwd // .workspace_roots[0] // empty')
+CWD="${CWD:-$PWD}"
+GIT_BRANCH=$(git -C "$CWD" rev-parse --abbrev-ref HEAD 2>/dev/null || true)
+REPOSITORY=$(git -C "$CWD" rev-parse --show-toplevel 2>/dev/null || true)
+MODEL=$(echo "$INPUT" | jq -r '.model // empty')
+AGENT_SESSION_ID=$(echo "$INPUT" | jq -r '.session_id // .conversation_id // empty')
+
+# Hooks run through a shell wrapper, so the agent is its parent.
+AGENT_PID=$PPID
+case "$(ps -o comm= -p "$AGENT_PID" 2>/dev/null)" in
+    sh|bash|dash|zsh) AGENT_PID=$(ps -o ppid= -p "$AGENT_PID" 2>/dev/null | tr -d ' ') ;;
+esac
+# Start time (field 22 of stat) lets the daemon tell a reused PID from the agent.
+AGENT_START=$(sed 's/.*) //' "/proc/$AGENT_PID/stat" 2>/dev/null | cut -d' ' -f20)
+
+if [ -n "$ZELLIJ_SESSION_NAME" ]; then
+    TERMINAL="zellij"
+elif [ -n "$TMUX" ]; then
+    TERMINAL="tmux"
+else
+    TERMINAL="${TERM_PROGRAM:-$TERM}"
+fi
+
 if [ -z "$ZELLIJ_SESSION_NAME" ]; then
     printf '\033]0;Argus (%s)\a' "$SESSION" > /dev/tty 2>/dev/null || true
 fi
 
 SOCK="${XDG_RUNTIME_DIR:-/tmp}/agents-monitor/daemon.sock"
-MSG="{\"type\":\"state\",\"session\":\"$SESSION\",\"state\":\"$STATE\",\"tool\":\"$TOOL\",\"agent_type\":\"$AGENT_TYPE\"}"
+MSG=$(jq -cn \
+    --arg session "$SESSION" --arg state "$STATE" --arg tool "$TOOL" --arg agent_type "$AGENT_TYPE" \
+    --arg event "$EVENT" --arg seq "$SEQ" \
+    --arg cwd "$CWD" --arg git_branch "$GIT_BRANCH" --arg pid "$AGENT_PID" --arg terminal "$TERMINAL" \
+    --arg pid_start_time "$AGENT_START" --arg repository "$REPOSITORY" \
+    --arg group "${ARGUS_GROUP:-}" --arg group_label "${ARGUS_GROUP_LABEL:-}" \
+    --arg parent_session "${PARENT_SESSION:-}" \
+    --arg model "$MODEL" --arg agent_session_id "$AGENT_SESSION_ID" \
+    '{type: "state", session: $session, state: $state, tool: $tool, agent_type: $agent_type, event: $event,
+      seq: ($seq | tonumber? // null)}
+     + ({parent_session: $parent_session, group: $group, group_label: $group_label, cwd: $cwd, git_branch: $git_branch, repository: $repository, pid: ($pid | tonumber? // null),
+         pid_start_time: ($pid_start_time | tonumber? // null), terminal: $terminal,
+         model: $model, agent_session_id: $agent_session_id}
+        | with_entries(select(.value != "" and .value != null)))')
 
 [ "${ARGUS_DEBUG:-}" = "1" ] && mkdir -p "$(dirname "$LOG")" && echo "$(date '+%H:%M:%S') $AGENT_TYPE $SESSION $STATE event=$EVENT tool=$TOOL" >> "$LOG" 2>/dev/null
 
 if command -v socat >/dev/null 2>&1; then
-  echo "$MSG" | timeout 2 socat - "UNIX-CONNECT:$SOCK" 2>/dev/null || true
+  echo "$MSG" | timeout 2 socat - "UNIX-CONNECT:$SOCK" >/dev/null 2>&1 || true
 elif command -v nc >/dev/null 2>&1; then
-  echo "$MSG" | timeout 2 nc -U "$SOCK" 2>/dev/null || true
+  echo "$MSG" | timeout 2 nc -U "$SOCK" >/dev/null 2>&1 || true
 fi